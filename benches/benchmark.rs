@@ -1,7 +1,8 @@
-use criterion::{Criterion, criterion_group, criterion_main};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 use jp_web_novel_text::{
-    DictionaryPhrase, DictionaryWord, DictionaryWordKeyPhrase, NewLinePhrase, Parser, Phrase,
-    PlainPhrase, PreparedDictionary, RubyPhrase, WhiteSpacePhrase, WhiteSpaceType,
+    DictionaryPhrase, DictionaryWord, DictionaryWordKeyPhrase, MappedDictionary, MappedParser,
+    NewLinePhrase, ParseText, Parser, Phrase, PlainPhrase, PreparedDictionary, RubyPhrase,
+    UpdatableParser, WhiteSpacePhrase, WhiteSpaceType,
 };
 
 fn benchmark_words() -> Vec<DictionaryWord> {
@@ -53,6 +54,38 @@ fn parse_kokoro_with_prepared_dictionary(c: &mut Criterion) {
     });
 }
 
+fn parse_kokoro_with_mapped_dictionary(c: &mut Criterion) {
+    let kokoro_body = include_str!("test_data/kokoro_utf8.txt");
+    let words = benchmark_words();
+    let bytes = MappedDictionary::prepare(&words).unwrap().into_inner();
+    c.bench_function("parse_kokoro_with_mapped_dictionary", |b| {
+        b.iter(|| {
+            let parser = MappedParser::<_, ()>::try_from_bytes(bytes.as_slice()).unwrap();
+            for _ in parser.parse_iter(kokoro_body) {}
+        });
+    });
+}
+
+fn load_prepared_dictionary(c: &mut Criterion) {
+    let words = benchmark_words();
+    let serialized = serde_cbor::to_vec(&PreparedDictionary::prepare(words).unwrap()).unwrap();
+    c.bench_function("load_prepared_dictionary", |b| {
+        b.iter(|| {
+            let pd =
+                serde_cbor::from_slice::<PreparedDictionary<DictionaryWord>>(&serialized).unwrap();
+            Parser::try_from(pd).unwrap()
+        });
+    });
+}
+
+fn load_mapped_dictionary(c: &mut Criterion) {
+    let words = benchmark_words();
+    let bytes = MappedDictionary::prepare(&words).unwrap().into_inner();
+    c.bench_function("load_mapped_dictionary", |b| {
+        b.iter(|| MappedParser::<_, ()>::try_from_bytes(bytes.as_slice()).unwrap());
+    });
+}
+
 fn parse_kokoro_with_dic_but_bench_parse_only(c: &mut Criterion) {
     let kokoro_body = include_str!("test_data/kokoro_utf8.txt");
     let words = benchmark_words();
@@ -68,6 +101,22 @@ fn build_dictionary(c: &mut Criterion) {
     });
 }
 
+fn update_dictionary(c: &mut Criterion) {
+    let words = benchmark_words();
+    let mut parser = UpdatableParser::try_new_with_dic(words).unwrap();
+    c.bench_function("update_dictionary", |b| {
+        b.iter(|| {
+            parser
+                .replace(DictionaryWord::new(
+                    "玄人".into(),
+                    "くろうと".into(),
+                    "".into(),
+                ))
+                .unwrap()
+        });
+    });
+}
+
 fn parse_kokoro_without_dictionary(c: &mut Criterion) {
     let kokoro_body = include_str!("test_data/kokoro_utf8.txt");
     c.bench_function("parse_kokoro_without_dictionary", |b| {
@@ -78,6 +127,57 @@ fn parse_kokoro_without_dictionary(c: &mut Criterion) {
     });
 }
 
+/// 閉じ括弧の無いルビ記法や長い漢字の並びだけでできた1行。長さを10倍にしても時間が10倍程度に収まることを見る
+fn parse_hostile_input(c: &mut Criterion) {
+    let parser = Parser::default();
+    let mut group = c.benchmark_group("parse_hostile_input");
+    for chars in [2_000, 20_000] {
+        for (name, text) in [
+            ("pipes", "|".repeat(chars)),
+            ("kanji", "漢".repeat(chars)),
+            ("unclosed_rubies", "漢《".repeat(chars / 2)),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, chars), &text, |b, text| {
+                b.iter(|| for _ in parser.parse_iter(text.as_str()) {});
+            });
+        }
+    }
+    group.finish();
+}
+
+/// 逐次の解析と並列の解析を比べる。長い文書を想定して、こころを繰り返したものも測る
+#[cfg(feature = "parallel")]
+fn parse_kokoro_parallel(c: &mut Criterion) {
+    let kokoro_body = include_str!("test_data/kokoro_utf8.txt");
+    let parser = Parser::try_new_with_dic(benchmark_words()).unwrap();
+    let mut group = c.benchmark_group("parse_kokoro_parallel");
+    for times in [1, 8] {
+        let text = kokoro_body.repeat(times);
+        group.bench_with_input(BenchmarkId::new("sequential", times), &text, |b, text| {
+            b.iter(|| parser.parse_iter(text.as_str()).collect::<Vec<_>>());
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", times), &text, |b, text| {
+            b.iter(|| parser.par_parse(text));
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "parallel"))]
+fn parse_kokoro_parallel(_: &mut Criterion) {}
+
+#[cfg(feature = "ropey")]
+fn parse_kokoro_rope(c: &mut Criterion) {
+    let rope = ropey::Rope::from_str(include_str!("test_data/kokoro_utf8.txt"));
+    let parser = Parser::try_new_with_dic(benchmark_words()).unwrap();
+    c.bench_function("parse_kokoro_rope", |b| {
+        b.iter(|| for _ in parser.parse_iter(jp_web_novel_text::RopeInput::from(&rope)) {});
+    });
+}
+
+#[cfg(not(feature = "ropey"))]
+fn parse_kokoro_rope(_: &mut Criterion) {}
+
 fn parse_kokoro_and_gen_html(c: &mut Criterion) {
     let kokoro_body = include_str!("test_data/kokoro_utf8.txt");
     let words = benchmark_words();
@@ -157,7 +257,14 @@ criterion_group!(
     parse_kokoro_and_gen_html,
     parse_kokoro_with_dic_but_bench_parse_only,
     build_dictionary,
+    update_dictionary,
     parse_kokoro_with_prepared_dictionary,
-    parse_kokoro_without_dictionary
+    parse_kokoro_with_mapped_dictionary,
+    load_prepared_dictionary,
+    load_mapped_dictionary,
+    parse_kokoro_without_dictionary,
+    parse_hostile_input,
+    parse_kokoro_parallel,
+    parse_kokoro_rope
 );
 criterion_main!(benches);
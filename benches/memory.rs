@@ -0,0 +1,150 @@
+//! 辞書を持つのに使うメモリを、割り当てを数えて測る。`cargo bench --bench memory`で表を出す
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use jp_web_novel_text::{
+    DictionaryWord, DictionaryWordKeyPhrase, MappedDictionary, MappedDictionaryBuilder,
+    MappedParser, ParseText, Parser, PreparedDictionary,
+};
+
+struct CountingAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+        LIVE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        LIVE_ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // 移動するときは古い領域と新しい領域が同時にある
+        let live = LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed) + new_size;
+        PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// `build`が返した値が持ち続けているバイト数と割り当ての数
+fn retained<T>(build: impl FnOnce() -> T) -> (T, usize, usize) {
+    let bytes = LIVE_BYTES.load(Ordering::Relaxed);
+    let allocations = LIVE_ALLOCATIONS.load(Ordering::Relaxed);
+    let value = build();
+    (
+        value,
+        LIVE_BYTES.load(Ordering::Relaxed) - bytes,
+        LIVE_ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+    )
+}
+
+/// `build`の間に、呼び出し前より余分に使ったバイト数の最大値
+fn peak<T>(build: impl FnOnce() -> T) -> (T, usize) {
+    let bytes = LIVE_BYTES.load(Ordering::Relaxed);
+    PEAK_BYTES.store(bytes, Ordering::Relaxed);
+    let value = build();
+    (value, PEAK_BYTES.load(Ordering::Relaxed) - bytes)
+}
+
+/// 漢字2字とかなの送りを持つ語を`count`個作る
+fn words(count: usize) -> Vec<DictionaryWord> {
+    word_iter(count).collect()
+}
+
+fn word_iter(count: usize) -> impl Iterator<Item = DictionaryWord> {
+    let kanji = ('\u{4E00}'..'\u{9FFF}').collect::<Vec<_>>();
+    (0..count).map(move |i| {
+        let target = format!("{}{}", kanji[i % kanji.len()], kanji[i / kanji.len()]);
+        DictionaryWord::new_all(
+            vec![
+                DictionaryWordKeyPhrase::new_ruby(target, "かんじ".into()),
+                DictionaryWordKeyPhrase::new_plain("しい".into()),
+            ],
+            format!("{i}番目の語"),
+            (),
+        )
+    })
+}
+
+fn main() {
+    let text = include_str!("test_data/kokoro_utf8.txt");
+    println!(
+        "{:<20} {:>8} {:>14} {:>12}",
+        "layout", "words", "bytes", "allocations"
+    );
+    for count in [30_000, 300_000] {
+        let source = words(count);
+        let report = |name: &str, bytes: usize, allocations: usize| {
+            println!("{name:<20} {count:>8} {bytes:>14} {allocations:>12}");
+        };
+
+        let (vec, bytes, allocations) = retained(|| source.clone());
+        report("Vec<DictionaryWord>", bytes, allocations);
+        drop(vec);
+
+        let (parser, bytes, allocations) = retained(|| {
+            let parser = Parser::try_new_with_dic(source.clone()).unwrap();
+            black_box(parser.parse_iter(text).count());
+            parser
+        });
+        report("Parser", bytes, allocations);
+        drop(parser);
+
+        let (parser, bytes, allocations) = retained(|| {
+            let parser = MappedParser::try_new_with_dic(&source).unwrap();
+            black_box(parser.parse_iter(text).count());
+            parser
+        });
+        report("MappedParser", bytes, allocations);
+        drop(parser);
+    }
+
+    // 語を作りながら辞書に詰めるときの、途中で使うメモリの最大値
+    println!();
+    println!("{:<20} {:>8} {:>14}", "build", "words", "peak bytes");
+    for count in [30_000, 300_000] {
+        let report = |name: &str, bytes: usize| {
+            println!("{name:<20} {count:>8} {bytes:>14}");
+        };
+
+        let (dic, bytes) = peak(|| PreparedDictionary::prepare(words(count)).unwrap());
+        report("prepare", bytes);
+        drop(dic);
+
+        let (dic, bytes) = peak(|| PreparedDictionary::prepare_iter(word_iter(count)).unwrap());
+        report("prepare_iter", bytes);
+        drop(dic);
+
+        let (dic, bytes) = peak(|| MappedDictionary::prepare(&words(count)).unwrap());
+        report("mapped prepare", bytes);
+        drop(dic);
+
+        let (dic, bytes) = peak(|| MappedDictionary::prepare_iter(word_iter(count)).unwrap());
+        report("mapped prepare_iter", bytes);
+        drop(dic);
+
+        let (written, bytes) = peak(|| {
+            let mut builder = MappedDictionaryBuilder::new();
+            builder.extend(word_iter(count)).unwrap();
+            builder.write_to(std::io::sink()).unwrap()
+        });
+        report("mapped write_to", bytes);
+        black_box(written);
+    }
+}
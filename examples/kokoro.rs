@@ -1,8 +1,8 @@
 use std::{env, fs::File, io::Write};
 
 use jp_web_novel_text::{
-    DictionaryPhrase, DictionaryWord, DictionaryWordKeyPhrase, NewLinePhrase, Parser, Phrase,
-    PlainPhrase, RubyPhrase, WhiteSpacePhrase, WhiteSpaceType,
+    DictionaryPhrase, DictionaryWord, DictionaryWordKeyPhrase, NewLinePhrase, ParseText, Parser,
+    Phrase, PlainPhrase, RubyPhrase, WhiteSpacePhrase, WhiteSpaceType,
 };
 
 fn words() -> Vec<DictionaryWord> {
@@ -11,7 +11,7 @@ fn words() -> Vec<DictionaryWord> {
             DictionaryWordKeyPhrase::new_ruby("若々".into(),"わかわか".into()),
             DictionaryWordKeyPhrase::new_plain("しい".into()),
         ], "年齢のわりに若く見えること、また、活力や気力が衰えていないこと".into(),()),
-        DictionaryWord::new("茶屋".into(), "ちゃや".into(), "製した茶を売る店。茶舗。".into()),
+        DictionaryWord::new("茶屋".into(), "ちゃや".into(), "|製茶《せいちゃ》を売る店。茶舗。".into()),
         DictionaryWord::new("自分".into(), "じぶん".into(), "その人自身。".into()),
         DictionaryWord::new("好奇心".into(), "こうきしん".into(), "新しいことや未知のこと、珍しい物事に対して強い興味や関心を持ち、それを知りたい、体験したいと思う心".into()),
         DictionaryWord::new(
@@ -115,7 +115,7 @@ fn main() {
             Phrase::Ruby(ruby) => emit_ruby(&mut buf, ruby),
             Phrase::NewLine(nl) => emit_newline(&mut buf, nl),
             Phrase::WhiteSpace(sp) => emit_space(&mut buf, sp),
-            Phrase::DictionaryWord(dw) => emit_dictionary_word(&mut buf, dw),
+            Phrase::DictionaryWord(dw) => emit_dictionary_word(&mut buf, &parser, dw),
         }
     }
     buf.push_str("</span></body></html>");
@@ -125,9 +125,24 @@ fn main() {
     output_file.write_all(buf.as_bytes()).unwrap();
 }
 
-fn emit_dictionary_word(buf: &mut String, dw: &DictionaryPhrase<&str, &DictionaryWord>) {
+fn emit_dictionary_word(
+    buf: &mut String,
+    parser: &Parser,
+    dw: &DictionaryPhrase<&str, &DictionaryWord>,
+) {
     buf.push_str("<span style=\"color:#0000FF\" class=\"c-tooltip\" data-tooltip=\"");
-    buf.push_str(dw.word().description());
+    // ツールチップは文字列しか表示できないので、ルビは括弧書きにする
+    for fragment in parser.parse_description(dw.word()) {
+        match fragment.phrase() {
+            Phrase::Ruby(ruby) => {
+                buf.push_str(ruby.target());
+                buf.push('(');
+                buf.push_str(ruby.ruby());
+                buf.push(')');
+            }
+            _ => buf.push_str(fragment.fragment()),
+        }
+    }
     buf.push_str("\">");
     for r in dw.word().phrase().iter() {
         match r {
@@ -0,0 +1,229 @@
+use std::io::Write;
+
+use derive_getters::Getters;
+use derive_new::new;
+
+use crate::{
+    DictionaryWord, Error, Result,
+    dictionary::kana::{is_kana_only, to_hiragana, to_katakana},
+};
+
+#[derive(new, Getters, Clone, PartialEq, Debug)]
+pub struct ImeExportOptions {
+    part_of_speech: String,
+}
+
+impl Default for ImeExportOptions {
+    fn default() -> Self {
+        Self::new("名詞".into())
+    }
+}
+
+#[derive(new, Getters, Clone, PartialEq, Debug)]
+pub struct MecabExportOptions {
+    left_id: u32,
+    right_id: u32,
+    cost: i32,
+    part_of_speech: String,
+}
+
+impl Default for MecabExportOptions {
+    fn default() -> Self {
+        Self::new(1285, 1285, 5000, "名詞,一般,*,*,*,*".into())
+    }
+}
+
+/// Google日本語入力のユーザー辞書(タブ区切りテキスト)を書き出す。
+/// 読みが仮名だけにならない語は登録できないので書き出さない。
+pub fn write_google_ime_dictionary<X>(
+    words: &[DictionaryWord<X>],
+    options: &ImeExportOptions,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut count = 0;
+    for (reading, word) in ime_entries(words) {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            reading,
+            word.key(),
+            options.part_of_speech,
+            single_line(word.description())
+        )
+        .map_err(Error::new_write_dictionary)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// MS-IMEのユーザー辞書(UTF-16LE、改行はCRLF)を書き出す。
+/// 読みが仮名だけにならない語は登録できないので書き出さない。
+pub fn write_ms_ime_dictionary<X>(
+    words: &[DictionaryWord<X>],
+    options: &ImeExportOptions,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut text = String::from("!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n\r\n");
+    let mut count = 0;
+    for (reading, word) in ime_entries(words) {
+        text.push_str(&reading);
+        text.push('\t');
+        text.push_str(word.key());
+        text.push('\t');
+        text.push_str(&options.part_of_speech);
+        text.push_str("\r\n");
+        count += 1;
+    }
+    let mut buf = Vec::with_capacity(text.len() * 2 + 2);
+    buf.extend_from_slice(&[0xFF, 0xFE]);
+    for u in text.encode_utf16() {
+        buf.extend_from_slice(&u.to_le_bytes());
+    }
+    writer
+        .write_all(&buf)
+        .map_err(Error::new_write_dictionary)?;
+    Ok(count)
+}
+
+/// MeCab(IPADIC)のユーザー辞書CSVを書き出す。読みと発音はカタカナにし、
+/// 仮名にならない場合は`*`にする。
+pub fn write_mecab_user_dictionary<X>(
+    words: &[DictionaryWord<X>],
+    options: &MecabExportOptions,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut count = 0;
+    for word in words.iter().filter(|w| !w.key().is_empty()) {
+        let reading = to_katakana(&word.reading());
+        let reading = if is_kana_only(&reading) {
+            csv_field(&reading)
+        } else {
+            "*".into()
+        };
+        let key = csv_field(word.key());
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            key,
+            options.left_id,
+            options.right_id,
+            options.cost,
+            options.part_of_speech,
+            key,
+            reading,
+            reading
+        )
+        .map_err(Error::new_write_dictionary)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn ime_entries<X>(
+    words: &[DictionaryWord<X>],
+) -> impl Iterator<Item = (String, &DictionaryWord<X>)> {
+    words.iter().filter_map(|word| {
+        let reading = to_hiragana(&word.reading());
+        is_kana_only(&reading).then_some((reading, word))
+    })
+}
+
+fn single_line(s: &str) -> String {
+    s.split(['\r', '\n', '\t'])
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DictionaryWordKeyPhrase;
+
+    use super::*;
+    use googletest::prelude::*;
+
+    fn words() -> Vec<DictionaryWord> {
+        vec![
+            DictionaryWord::new_all(
+                vec![
+                    DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                    DictionaryWordKeyPhrase::new_plain("しい".into()),
+                ],
+                "年齢のわりに若く見えること\nまた、活力や気力が衰えていないこと".into(),
+                (),
+            ),
+            DictionaryWord::new("魔導".into(), "マドウ".into(), "魔法の技術".into()),
+            DictionaryWord::new("どうして".into(), "".into(), "なぜ。".into()),
+            DictionaryWord::new("問答".into(), "".into(), "読みが無い".into()),
+        ]
+    }
+
+    #[gtest]
+    fn write_google_ime_dictionary_works() -> anyhow::Result<()> {
+        let mut buf = vec![];
+        let count = write_google_ime_dictionary(&words(), &ImeExportOptions::default(), &mut buf)?;
+        assert_that!(count, eq(3));
+        assert_that!(
+            String::from_utf8(buf)?,
+            eq(
+                "わかわかしい\t若々しい\t名詞\t年齢のわりに若く見えること また、活力や気力が衰えていないこと\n\
+                まどう\t魔導\t名詞\t魔法の技術\n\
+                どうして\tどうして\t名詞\tなぜ。\n"
+            )
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn write_ms_ime_dictionary_works() -> anyhow::Result<()> {
+        let mut buf = vec![];
+        let count =
+            write_ms_ime_dictionary(&words(), &ImeExportOptions::new("人名".into()), &mut buf)?;
+        assert_that!(count, eq(3));
+        assert_that!(buf[..2], eq(&[0xFF, 0xFE]));
+        let units = buf[2..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        assert_that!(
+            String::from_utf16(&units)?,
+            eq("!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n\r\n\
+                わかわかしい\t若々しい\t人名\r\n\
+                まどう\t魔導\t人名\r\n\
+                どうして\tどうして\t人名\r\n")
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn write_mecab_user_dictionary_works() -> anyhow::Result<()> {
+        let mut buf = vec![];
+        let mut words = words();
+        words.push(DictionaryWord::new(
+            "A,B".into(),
+            "えーびー".into(),
+            "".into(),
+        ));
+        let count = write_mecab_user_dictionary(&words, &MecabExportOptions::default(), &mut buf)?;
+        assert_that!(count, eq(5));
+        assert_that!(
+            String::from_utf8(buf)?,
+            eq(
+                "若々しい,1285,1285,5000,名詞,一般,*,*,*,*,若々しい,ワカワカシイ,ワカワカシイ\n\
+                魔導,1285,1285,5000,名詞,一般,*,*,*,*,魔導,マドウ,マドウ\n\
+                どうして,1285,1285,5000,名詞,一般,*,*,*,*,どうして,ドウシテ,ドウシテ\n\
+                問答,1285,1285,5000,名詞,一般,*,*,*,*,問答,*,*\n\
+                \"A,B\",1285,1285,5000,名詞,一般,*,*,*,*,\"A,B\",エービー,エービー\n"
+            )
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+const KATAKANA_TO_HIRAGANA_OFFSET: u32 = 0x60;
+
+pub(crate) const fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309F}')
+}
+
+pub(crate) const fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A0}'..='\u{30FF}')
+}
+
+pub(crate) const fn is_kana(c: char) -> bool {
+    is_hiragana(c) || is_katakana(c)
+}
+
+pub(crate) fn is_kana_only(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_kana)
+}
+
+pub(crate) fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' | 'ヽ' | 'ヾ' => {
+                char::from_u32(c as u32 - KATAKANA_TO_HIRAGANA_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+pub(crate) fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => {
+                char::from_u32(c as u32 + KATAKANA_TO_HIRAGANA_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    #[rstest]
+    #[case("たいほう", true)]
+    #[case("タイホウ", true)]
+    #[case("ヴァイオリン", true)]
+    #[case("大砲", false)]
+    #[case("", false)]
+    fn is_kana_only_works(#[case] s: &str, #[case] expected: bool) {
+        assert_that!(is_kana_only(s), eq(expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("タイホウ", "たいほう")]
+    #[case("ヴァイオリンとチャヤ", "ゔぁいおりんとちゃや")]
+    #[case("ラーメン", "らーめん")]
+    #[case("大砲", "大砲")]
+    fn to_hiragana_works(#[case] s: &str, #[case] expected: &str) {
+        assert_that!(to_hiragana(s), eq(expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("たいほう", "タイホウ")]
+    #[case("ゔぁいおりんとチャヤ", "ヴァイオリントチャヤ")]
+    #[case("らーめん", "ラーメン")]
+    #[case("大砲", "大砲")]
+    fn to_katakana_works(#[case] s: &str, #[case] expected: &str) {
+        assert_that!(to_katakana(s), eq(expected));
+    }
+}
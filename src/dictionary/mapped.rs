@@ -0,0 +1,835 @@
+//! メモリマップしてそのまま引ける辞書形式。
+//!
+//! ```text
+//! header  : magic(8) version(u32) word_count(u32) [offset(u32) len(u32)] x 6
+//! words   : 語ごとの固定長レコード
+//! phrases : キーの区切りごとの固定長レコード
+//! tags    : タグ文字列への参照
+//! strings : UTF-8文字列を詰めた領域
+//! blobs   : postcardでシリアライズした`extra`を詰めた領域
+//! trie    : crawdadのトライ。値は語の番号
+//! ```
+//!
+//! ヘッダーの領域の表はtrie、words、phrases、tags、strings、blobsの順に並ぶ。
+//! 読むときは領域の位置をこの表から得るので、本体での並びは問わない。
+//! 数値は全てリトルエンディアンで、アラインメントは仮定しない。
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::Debug,
+    hash::{BuildHasher, RandomState},
+    io::Write,
+    marker::PhantomData,
+    ops::Range,
+};
+
+use crawdad::Trie;
+use nom::Input;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DictionaryWord, DictionaryWordKeyPhrase, Error, Result,
+    dictionary::validation::explain_build_error, general_parser::WordLookup,
+    parser::trie_view::TrieView,
+};
+
+const MAGIC: &[u8; 8] = b"JWNTDIC\0";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_BYTES: usize = 16 + SECTION_COUNT * 8;
+const SECTION_COUNT: usize = 6;
+const WORD_BYTES: usize = 40;
+const PHRASE_BYTES: usize = 20;
+const REF_BYTES: usize = 8;
+
+const PHRASE_KIND_PLAIN: u32 = 0;
+const PHRASE_KIND_RUBY: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sections {
+    word_count: u32,
+    trie: Range<usize>,
+    words: Range<usize>,
+    phrases: Range<usize>,
+    tags: Range<usize>,
+    strings: Range<usize>,
+    blobs: Range<usize>,
+}
+
+impl Sections {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let header = bytes
+            .get(..HEADER_BYTES)
+            .ok_or(Error::InvalidMappedDictionary("ヘッダーが足りません"))?;
+        if &header[..8] != MAGIC {
+            return Err(Error::InvalidMappedDictionary("形式が異なります"));
+        }
+        if read_u32(header, 8) != Some(FORMAT_VERSION) {
+            return Err(Error::InvalidMappedDictionary("版が異なります"));
+        }
+        let word_count = read_u32(header, 12).unwrap_or_default();
+        let section = |i: usize| -> Result<Range<usize>> {
+            let offset = read_u32(header, 16 + i * 8).unwrap_or_default() as usize;
+            let len = read_u32(header, 20 + i * 8).unwrap_or_default() as usize;
+            let range = offset..offset + len;
+            if offset < HEADER_BYTES || range.end > bytes.len() {
+                Err(Error::InvalidMappedDictionary("領域が範囲外です"))
+            } else {
+                Ok(range)
+            }
+        };
+        let sections = Self {
+            word_count,
+            trie: section(0)?,
+            words: section(1)?,
+            phrases: section(2)?,
+            tags: section(3)?,
+            strings: section(4)?,
+            blobs: section(5)?,
+        };
+        if sections.words.len() != word_count as usize * WORD_BYTES
+            || !sections.phrases.len().is_multiple_of(PHRASE_BYTES)
+            || !sections.tags.len().is_multiple_of(REF_BYTES)
+        {
+            return Err(Error::InvalidMappedDictionary("レコードの長さが不正です"));
+        }
+        if word_count > 0 && TrieView::parse(&bytes[sections.trie.clone()]).is_none() {
+            return Err(Error::InvalidMappedDictionary("トライが壊れています"));
+        }
+        Ok(sections)
+    }
+}
+
+/// 語を1つずつ受け取り、[`MappedDictionary`]の形式に詰めていく。
+///
+/// 受け取った語はすぐに詰めて手放すので、語の並び全体を持つ必要がない。
+/// 語を詰める間、詰めた領域のほかに持つのは文字列を共有するためのハッシュ値の表だけ。
+/// ただしトライを作る間は、crawdadがすべての見出しを`char`の並びに写し取るので、
+/// 見出しの合計文字数の4倍と語ごとに数十バイトを一時的に使う。
+/// 語のレコードの前にヘッダーの場所を空けておき、最後に残りの領域をその後ろへ繋げるので、
+/// 辞書全体の複製は作らない
+pub struct MappedDictionaryBuilder<X = ()> {
+    /// 先頭の[`HEADER_BYTES`]はヘッダーの場所
+    words: Vec<u8>,
+    phrases: Vec<u8>,
+    tags: Vec<u8>,
+    strings: Vec<u8>,
+    blobs: Vec<u8>,
+    /// 文字列のハッシュ値から、最初に詰めた位置。衝突したときは共有せずに詰める
+    string_refs: HashMap<u64, (u32, u32)>,
+    hasher: RandomState,
+    _extra: PhantomData<fn(X)>,
+}
+
+impl<X> Default for MappedDictionaryBuilder<X> {
+    fn default() -> Self {
+        Self {
+            words: vec![0; HEADER_BYTES],
+            phrases: vec![],
+            tags: vec![],
+            strings: vec![],
+            blobs: vec![],
+            string_refs: HashMap::new(),
+            hasher: RandomState::new(),
+            _extra: PhantomData,
+        }
+    }
+}
+
+impl<X> MappedDictionaryBuilder<X>
+where
+    X: Serialize,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        (self.words.len() - HEADER_BYTES) / WORD_BYTES
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push(&mut self, word: &DictionaryWord<X>) -> Result<()> {
+        let key = self.push_string(word.key())?;
+        let description = self.push_string(word.description())?;
+        let extra_bytes =
+            postcard::to_allocvec(word.extra()).map_err(Error::new_serialize_dictionary_extra)?;
+        let extra = (to_u32(self.blobs.len())?, to_u32(extra_bytes.len())?);
+        self.blobs.extend_from_slice(&extra_bytes);
+
+        let phrase_start = to_u32(self.phrases.len() / PHRASE_BYTES)?;
+        let mut target_offset = key.0;
+        for phrase in word.phrase() {
+            let (kind, target, ruby) = match phrase {
+                DictionaryWordKeyPhrase::Plain { target } => (PHRASE_KIND_PLAIN, target, (0, 0)),
+                DictionaryWordKeyPhrase::Ruby { target, ruby } => {
+                    (PHRASE_KIND_RUBY, target, self.push_string(ruby)?)
+                }
+            };
+            // キーは区切りを繋げたものなので、区切りの表記はキーの一部を指す
+            let target = (target_offset, to_u32(target.len())?);
+            target_offset += target.1;
+            push_u32(&mut self.phrases, kind);
+            push_ref(&mut self.phrases, target);
+            push_ref(&mut self.phrases, ruby);
+        }
+
+        let tag_start = to_u32(self.tags.len() / REF_BYTES)?;
+        for tag in word.tags() {
+            let tag = self.push_string(tag)?;
+            push_ref(&mut self.tags, tag);
+        }
+
+        push_ref(&mut self.words, key);
+        push_ref(&mut self.words, description);
+        push_ref(&mut self.words, extra);
+        push_u32(&mut self.words, phrase_start);
+        push_u32(&mut self.words, to_u32(word.phrase().len())?);
+        push_u32(&mut self.words, tag_start);
+        push_u32(&mut self.words, to_u32(word.tags().len())?);
+        Ok(())
+    }
+
+    /// 語を順に詰める。イテレーターが所有する語を返すなら、語は詰めたそばから破棄される
+    pub fn extend<I, W>(&mut self, words: I) -> Result<()>
+    where
+        I: IntoIterator<Item = W>,
+        W: Borrow<DictionaryWord<X>>,
+    {
+        words
+            .into_iter()
+            .try_for_each(|word| self.push(word.borrow()))
+    }
+
+    /// 見出しからトライを作り、メモリ上の辞書にする。
+    /// 語のレコードの後ろへ残りの領域を繋げ、繋げた領域はすぐに手放す
+    pub fn finish(mut self) -> Result<MappedDictionary<Vec<u8>, X>> {
+        let trie = self.build_trie()?;
+        let (mut bytes, rest) = self.into_parts(trie)?;
+        bytes.reserve_exact(rest.iter().map(Vec::len).sum());
+        for section in rest {
+            bytes.extend_from_slice(&section);
+        }
+        MappedDictionary::try_new(bytes)
+    }
+
+    /// 見出しからトライを作り、辞書のバイト列を`writer`へ書き出す。
+    /// [`MappedDictionaryBuilder::finish`]と違い、領域を1つのバイト列にまとめ直さない
+    pub fn write_to<W>(mut self, mut writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        let trie = self.build_trie()?;
+        let (bytes, rest) = self.into_parts(trie)?;
+        writer
+            .write_all(&bytes)
+            .map_err(Error::new_write_dictionary)?;
+        for section in rest {
+            writer
+                .write_all(&section)
+                .map_err(Error::new_write_dictionary)?;
+        }
+        writer.flush().map_err(Error::new_write_dictionary)
+    }
+
+    fn push_string(&mut self, s: &str) -> Result<(u32, u32)> {
+        let hash = self.hasher.hash_one(s);
+        if let Some(&(offset, len)) = self.string_refs.get(&hash)
+            && &self.strings[offset as usize..(offset + len) as usize] == s.as_bytes()
+        {
+            return Ok((offset, len));
+        }
+        let r = (to_u32(self.strings.len())?, to_u32(s.len())?);
+        self.strings.extend_from_slice(s.as_bytes());
+        self.string_refs.entry(hash).or_insert(r);
+        Ok(r)
+    }
+
+    /// `index`番目の語の見出し
+    fn key(&self, index: usize) -> &str {
+        let (offset, len) = read_ref(&self.words, HEADER_BYTES + index * WORD_BYTES);
+        std::str::from_utf8(&self.strings[offset as usize..(offset + len) as usize])
+            .expect("詰めた見出しはUTF-8")
+    }
+
+    /// 詰め終えたら要らない文字列の表と、領域の余りを手放してからトライを作る。
+    /// crawdadは見出しを写し取って並べ替えるので、ここで見出しの分だけ一時的にメモリが増える。
+    /// 語のレコードの領域は後で伸ばすので縮めない
+    fn build_trie(&mut self) -> Result<Vec<u8>> {
+        self.string_refs = HashMap::new();
+        for section in [
+            &mut self.phrases,
+            &mut self.tags,
+            &mut self.strings,
+            &mut self.blobs,
+        ] {
+            section.shrink_to_fit();
+        }
+        if self.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(Trie::from_keys((0..self.len()).map(|i| self.key(i)))
+            .map_err(|e| explain_build_error(&self.key_words(), Error::new_create_dictionary(e)))?
+            .serialize_to_vec())
+    }
+
+    /// 原因の語を示すために、詰めた語の見出しと区切りだけを取り出す
+    fn key_words(&self) -> Vec<DictionaryWord> {
+        let string = |(offset, len): (u32, u32)| {
+            String::from_utf8_lossy(&self.strings[offset as usize..(offset + len) as usize])
+                .into_owned()
+        };
+        (0..self.len())
+            .map(|i| {
+                let (start, count) =
+                    read_ref(&self.words, HEADER_BYTES + i * WORD_BYTES + 3 * REF_BYTES);
+                let phrases = (start..start + count)
+                    .map(|p| {
+                        let record = p as usize * PHRASE_BYTES;
+                        let target = string(read_ref(&self.phrases, record + 4));
+                        if read_u32(&self.phrases, record) == Some(PHRASE_KIND_RUBY) {
+                            let ruby = string(read_ref(&self.phrases, record + 12));
+                            DictionaryWordKeyPhrase::new_ruby(target, ruby)
+                        } else {
+                            DictionaryWordKeyPhrase::new_plain(target)
+                        }
+                    })
+                    .collect();
+                DictionaryWord::new_all(phrases, "".into(), ())
+            })
+            .collect()
+    }
+
+    /// ヘッダーを書き込んだ語のレコードの領域と、その後ろに続く残りの領域。
+    /// トライは最後に作るので末尾に置く
+    fn into_parts(self, trie: Vec<u8>) -> Result<(Vec<u8>, [Vec<u8>; SECTION_COUNT - 1])> {
+        let word_count = to_u32(self.len())?;
+        let mut bytes = self.words;
+        let rest = [self.phrases, self.tags, self.strings, self.blobs, trie];
+        let mut offset = bytes.len();
+        let mut refs = vec![(to_u32(HEADER_BYTES)?, to_u32(offset - HEADER_BYTES)?)];
+        for section in &rest {
+            refs.push((to_u32(offset)?, to_u32(section.len())?));
+            offset += section.len();
+        }
+        to_u32(offset)?;
+        // ヘッダーの表ではトライが先頭
+        refs.rotate_right(1);
+
+        let mut header = Vec::with_capacity(HEADER_BYTES);
+        header.extend_from_slice(MAGIC);
+        push_u32(&mut header, FORMAT_VERSION);
+        push_u32(&mut header, word_count);
+        for r in refs {
+            push_ref(&mut header, r);
+        }
+        bytes[..HEADER_BYTES].copy_from_slice(&header);
+        Ok((bytes, rest))
+    }
+}
+
+/// 語をまとめて読み込まずに、必要になった語だけをバイト列から直接引く辞書。
+///
+/// `B`には`Vec<u8>`や`&[u8]`のほか、`memmap2::Mmap`など`AsRef<[u8]>`を満たす型を使える。
+pub struct MappedDictionary<B = Vec<u8>, X = ()>
+where
+    B: AsRef<[u8]>,
+{
+    bytes: B,
+    sections: Sections,
+    _extra: PhantomData<fn() -> X>,
+}
+
+impl<X> MappedDictionary<Vec<u8>, X>
+where
+    X: Serialize,
+{
+    pub fn prepare(words: &[DictionaryWord<X>]) -> Result<Self> {
+        Self::prepare_iter(words)
+    }
+
+    /// 語の並びを1回たどって詰める。[`MappedDictionaryBuilder`]を使う
+    pub fn prepare_iter<I, W>(words: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = W>,
+        W: Borrow<DictionaryWord<X>>,
+    {
+        let mut builder = MappedDictionaryBuilder::new();
+        builder.extend(words)?;
+        builder.finish()
+    }
+}
+
+impl<B, X> MappedDictionary<B, X>
+where
+    B: AsRef<[u8]>,
+{
+    pub fn try_new(bytes: B) -> Result<Self> {
+        let sections = Sections::parse(bytes.as_ref())?;
+        Ok(Self {
+            bytes,
+            sections,
+            _extra: PhantomData,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.sections.word_count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// キーが完全に一致する語
+    pub fn get(&self, key: &str) -> Option<MappedWord<'_, X>> {
+        self.word(self.trie()?.exact_match(key.chars())? as usize)
+    }
+
+    pub fn word(&self, index: usize) -> Option<MappedWord<'_, X>> {
+        (index < self.len()).then(|| MappedWord {
+            bytes: self.bytes.as_ref(),
+            sections: &self.sections,
+            index,
+            _extra: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn trie(&self) -> Option<TrieView<'_>> {
+        if self.is_empty() {
+            None
+        } else {
+            TrieView::parse(&self.bytes.as_ref()[self.sections.trie.clone()]).map(|(t, _)| t)
+        }
+    }
+
+    #[inline]
+    fn longest_match<S>(&self, key: S) -> Option<(usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        self.trie()?
+            .common_prefix_search(key.iter_elements())
+            .last()
+            .map(|(i, length)| (i as usize, length))
+    }
+}
+
+impl<'a, B, X> WordLookup<'a> for MappedDictionary<B, X>
+where
+    B: AsRef<[u8]>,
+    X: 'a,
+{
+    type Word = MappedWord<'a, X>;
+
+    #[inline]
+    fn lookup<S>(&'a self, text: S) -> Option<(Self::Word, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        let (i, chars) = self.longest_match(text.clone())?;
+        Some((self.word(i)?, text.slice_index(chars).ok()?))
+    }
+
+    #[inline]
+    fn may_start_with(&self, c: char) -> bool {
+        self.trie().is_some_and(|trie| trie.has_child_of_root(c))
+    }
+}
+
+/// [`MappedDictionary`]の一語を指す。中身は参照されたときに読み出す
+pub struct MappedWord<'a, X = ()> {
+    bytes: &'a [u8],
+    sections: &'a Sections,
+    index: usize,
+    _extra: PhantomData<fn() -> X>,
+}
+
+impl<X> Clone for MappedWord<'_, X> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<X> Copy for MappedWord<'_, X> {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MappedKeyPhrase<'a> {
+    Plain { target: &'a str },
+    Ruby { target: &'a str, ruby: &'a str },
+}
+
+impl From<MappedKeyPhrase<'_>> for DictionaryWordKeyPhrase {
+    fn from(value: MappedKeyPhrase<'_>) -> Self {
+        match value {
+            MappedKeyPhrase::Plain { target } => Self::new_plain(target.into()),
+            MappedKeyPhrase::Ruby { target, ruby } => Self::new_ruby(target.into(), ruby.into()),
+        }
+    }
+}
+
+impl<'a, X> MappedWord<'a, X> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn key(&self) -> &'a str {
+        self.string(self.word_ref(0))
+    }
+
+    pub fn description(&self) -> &'a str {
+        self.string(self.word_ref(1))
+    }
+
+    pub fn phrase(&self) -> impl Iterator<Item = MappedKeyPhrase<'a>> + 'a {
+        let (start, count) = self.word_ref(3);
+        let (bytes, sections) = (self.bytes, self.sections);
+        (start..start.saturating_add(count)).map_while(move |i| {
+            let record = section(bytes, &sections.phrases)
+                .get(i as usize * PHRASE_BYTES..(i as usize + 1) * PHRASE_BYTES)?;
+            let target = string(bytes, sections, read_ref(record, 4));
+            Some(if read_u32(record, 0)? == PHRASE_KIND_RUBY {
+                MappedKeyPhrase::Ruby {
+                    target,
+                    ruby: string(bytes, sections, read_ref(record, 12)),
+                }
+            } else {
+                MappedKeyPhrase::Plain { target }
+            })
+        })
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &'a str> + 'a {
+        let (start, count) = self.word_ref(4);
+        let (bytes, sections) = (self.bytes, self.sections);
+        (start..start.saturating_add(count)).map_while(move |i| {
+            let record = section(bytes, &sections.tags)
+                .get(i as usize * REF_BYTES..(i as usize + 1) * REF_BYTES)?;
+            Some(string(bytes, sections, read_ref(record, 0)))
+        })
+    }
+
+    /// ルビの付いた部分はルビを、それ以外は表記をそのまま繋げた読み
+    pub fn reading(&self) -> String {
+        self.phrase()
+            .map(|p| match p {
+                MappedKeyPhrase::Plain { target } => target,
+                MappedKeyPhrase::Ruby { target: _, ruby } => ruby,
+            })
+            .collect()
+    }
+
+    pub fn extra(&self) -> Result<X>
+    where
+        X: Deserialize<'a>,
+    {
+        let (offset, len) = self.word_ref(2);
+        let blob = section(self.bytes, &self.sections.blobs)
+            .get(offset as usize..offset as usize + len as usize)
+            .unwrap_or_default();
+        postcard::from_bytes(blob).map_err(Error::new_deserialize_dictionary_extra)
+    }
+
+    pub fn to_word(&self) -> Result<DictionaryWord<X>>
+    where
+        X: Deserialize<'a>,
+    {
+        Ok(DictionaryWord::new_all(
+            self.phrase().map(DictionaryWordKeyPhrase::from).collect(),
+            self.description().into(),
+            self.extra()?,
+        )
+        .with_tags(self.tags().map(String::from).collect()))
+    }
+
+    fn word_ref(&self, field: usize) -> (u32, u32) {
+        section(self.bytes, &self.sections.words)
+            .get(self.index * WORD_BYTES..(self.index + 1) * WORD_BYTES)
+            .map(|record| read_ref(record, field * REF_BYTES))
+            .unwrap_or_default()
+    }
+
+    fn string(&self, r: (u32, u32)) -> &'a str {
+        string(self.bytes, self.sections, r)
+    }
+}
+
+impl<X> PartialEq for MappedWord<'_, X> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+            && self.description() == other.description()
+            && self.phrase().eq(other.phrase())
+            && self.tags().eq(other.tags())
+            && self.word_ref(2) == other.word_ref(2)
+    }
+}
+
+impl<X> Debug for MappedWord<'_, X> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedWord")
+            .field("index", &self.index)
+            .field("key", &self.key())
+            .field("phrase", &self.phrase().collect::<Vec<_>>())
+            .field("description", &self.description())
+            .field("tags", &self.tags().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+fn section<'a>(bytes: &'a [u8], range: &Range<usize>) -> &'a [u8] {
+    bytes.get(range.clone()).unwrap_or_default()
+}
+
+/// 壊れた参照は空文字列として読む
+fn string<'a>(bytes: &'a [u8], sections: &Sections, (offset, len): (u32, u32)) -> &'a str {
+    section(bytes, &sections.strings)
+        .get(offset as usize..offset as usize + len as usize)
+        .and_then(|s| std::str::from_utf8(s).ok())
+        .unwrap_or_default()
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_ref(bytes: &[u8], offset: usize) -> (u32, u32) {
+    (
+        read_u32(bytes, offset).unwrap_or_default(),
+        read_u32(bytes, offset + 4).unwrap_or_default(),
+    )
+}
+
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_ref(buf: &mut Vec<u8>, (offset, len): (u32, u32)) {
+    push_u32(buf, offset);
+    push_u32(buf, len);
+}
+
+fn to_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| Error::InvalidMappedDictionary("辞書が大きすぎます"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    struct Extra {
+        frequency: u32,
+        note: String,
+    }
+
+    fn words() -> Vec<DictionaryWord<Extra>> {
+        vec![
+            DictionaryWord::new_all(
+                vec![
+                    DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                    DictionaryWordKeyPhrase::new_plain("しい".into()),
+                ],
+                "年齢のわりに若く見えること".into(),
+                Extra {
+                    frequency: 3,
+                    note: "形容詞".into(),
+                },
+            )
+            .with_tags(vec!["adj-i".into(), "common".into()]),
+            DictionaryWord::new_all(
+                vec![DictionaryWordKeyPhrase::new_ruby(
+                    "茶屋".into(),
+                    "ちゃや".into(),
+                )],
+                "製した茶を売る店。".into(),
+                Extra {
+                    frequency: 10,
+                    note: "".into(),
+                },
+            )
+            .with_tags(vec!["common".into()]),
+            DictionaryWord::new_all(
+                vec![DictionaryWordKeyPhrase::new_plain("どうして".into())],
+                "製した茶を売る店。".into(),
+                Extra {
+                    frequency: 0,
+                    note: "同じ説明".into(),
+                },
+            ),
+        ]
+    }
+
+    #[gtest]
+    fn prepare_and_read_works() -> anyhow::Result<()> {
+        let words = words();
+        let prepared = MappedDictionary::prepare(&words)?;
+        let dic = MappedDictionary::<_, Extra>::try_new(prepared.as_bytes())?;
+        assert_that!(dic.len(), eq(words.len()));
+        for (i, word) in words.iter().enumerate() {
+            let mapped = dic.word(i).unwrap();
+            assert_that!(mapped.key(), eq(word.key()));
+            assert_that!(mapped.reading(), eq(&word.reading()));
+            assert_that!(mapped.to_word()?, eq(word));
+        }
+        assert_that!(dic.word(words.len()), none());
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("茶屋", Some(1))]
+    #[case("若々しい", Some(0))]
+    #[case("若々", None)]
+    #[case("茶屋で", None)]
+    fn get_works(#[case] key: &str, #[case] expected: Option<usize>) -> anyhow::Result<()> {
+        let dic = MappedDictionary::prepare(&words())?;
+        assert_that!(dic.get(key).map(|w| w.index()), eq(expected));
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("茶屋に行く", Some((1, "茶屋".len())))]
+    #[case("若々しい人", Some((0, "若々しい".len())))]
+    #[case("若々", None)]
+    fn lookup_works(
+        #[case] text: &str,
+        #[case] expected: Option<(usize, usize)>,
+    ) -> anyhow::Result<()> {
+        let dic = MappedDictionary::prepare(&words())?;
+        assert_that!(
+            dic.lookup(text).map(|(w, len)| (w.index(), len)),
+            eq(expected)
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn prepare_shares_strings() -> anyhow::Result<()> {
+        let dic = MappedDictionary::prepare(&words())?;
+        let strings = &dic.as_bytes()[dic.sections.strings.clone()];
+        let text = std::str::from_utf8(strings)?;
+        assert_that!(text.matches("製した茶を売る店。").count(), eq(1));
+        assert_that!(text.matches("茶屋").count(), eq(1));
+        Ok(())
+    }
+
+    /// [`words`]を書き出したバイト列のうち、トライの手前まで。
+    /// トライは版を固定したcrawdadが作るものなので、crawdadで作り直して比べる
+    const WORDS_BEFORE_TRIE: &[u8] = include_bytes!("test_data/mapped/words_before_trie.bin");
+
+    #[gtest]
+    fn builder_output_matches_fixture() -> anyhow::Result<()> {
+        let trie = Trie::from_keys(words().iter().map(|w| w.key()))
+            .unwrap()
+            .serialize_to_vec();
+        let expected = [WORDS_BEFORE_TRIE, &trie].concat();
+
+        let mut builder = MappedDictionaryBuilder::new();
+        builder.extend(words())?;
+        assert_that!(builder.len(), eq(words().len()));
+        assert_that!(builder.finish()?.into_inner(), eq(&expected));
+
+        let mut written = vec![];
+        let mut streamed = MappedDictionaryBuilder::new();
+        streamed.extend(words())?;
+        streamed.write_to(&mut written)?;
+        assert_that!(written, eq(&expected));
+
+        assert_that!(
+            MappedDictionary::prepare(&words())?.into_inner(),
+            eq(&expected)
+        );
+
+        let dic = MappedDictionary::<_, Extra>::try_new(expected)?;
+        assert_that!(
+            (0..dic.len())
+                .map(|i| dic.word(i).unwrap().to_word())
+                .collect::<crate::Result<Vec<_>>>()?,
+            eq(&words())
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn builder_explains_invalid_words() {
+        let words = [
+            DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into()),
+            DictionaryWord::new("".into(), "".into(), "".into()),
+            DictionaryWord::new("茶屋".into(), "".into(), "".into()),
+        ];
+        let mut builder = MappedDictionaryBuilder::new();
+        assert_that!(builder.extend(&words), ok(anything()));
+        assert_that!(
+            builder.finish().map(|_| ()),
+            err(matches_pattern!(Error::InvalidDictionaryWords(
+                elements_are![
+                    eq(&crate::DictionaryIssue::new(
+                        1,
+                        crate::DictionaryIssueKind::EmptyKey
+                    )),
+                    eq(&crate::DictionaryIssue::new(
+                        2,
+                        crate::DictionaryIssueKind::DuplicateKey { first: 0 }
+                    )),
+                ]
+            )))
+        );
+    }
+
+    #[gtest]
+    fn empty_dictionary_works() -> anyhow::Result<()> {
+        let dic = MappedDictionary::<_, ()>::prepare(&[])?;
+        assert_that!(dic.is_empty(), eq(true));
+        assert_that!(dic.lookup("茶屋").map(|(w, _)| w.index()), none());
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case::magic(0, "形式が異なります")]
+    #[case::version(8, "版が異なります")]
+    #[case::section(16, "領域が範囲外です")]
+    fn try_new_rejects_broken_header(
+        #[case] at: usize,
+        #[case] message: &str,
+    ) -> anyhow::Result<()> {
+        let mut bytes = MappedDictionary::prepare(&words())?.into_inner();
+        bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_that!(
+            MappedDictionary::<_, Extra>::try_new(bytes).map(|_| ()),
+            err(displays_as(contains_substring(message)))
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn truncated_bytes_never_panic() -> anyhow::Result<()> {
+        let bytes = MappedDictionary::prepare(&words())?.into_inner();
+        for len in 0..bytes.len() {
+            if let Ok(dic) = MappedDictionary::<_, Extra>::try_new(&bytes[..len]) {
+                for i in 0..dic.len() {
+                    let _ = dic.word(i).map(|w| w.to_word());
+                }
+                let _ = dic.lookup("若々しい");
+            }
+        }
+        Ok(())
+    }
+}
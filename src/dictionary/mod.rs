@@ -0,0 +1,576 @@
+mod export;
+pub(crate) mod kana;
+mod mapped;
+pub(crate) mod reading_index;
+#[cfg(feature = "toml")]
+mod source;
+pub(crate) mod validation;
+#[cfg(feature = "yomitan")]
+mod yomitan;
+
+use derive_getters::Getters;
+
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+
+use std::{fmt::Debug, marker::PhantomData};
+
+use crawdad::Trie;
+
+use crate::{
+    DictionaryBackend, Error, Result, general_parser::DictionaryWordContainer,
+    reading_index::ReadingIndex,
+};
+
+pub use export::*;
+pub use mapped::*;
+#[cfg(feature = "toml")]
+pub use source::*;
+pub use validation::*;
+#[cfg(feature = "yomitan")]
+pub use yomitan::*;
+
+#[derive(Clone, new, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DictionaryWordKeyPhrase {
+    Plain { target: String },
+    Ruby { target: String, ruby: String },
+}
+
+#[derive(Getters, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DictionaryWord<X = ()> {
+    key: String,
+    phrase: Vec<DictionaryWordKeyPhrase>,
+    description: String,
+    /// [`PreparedDictionary`]の版1.1.0で加えた。1.0.0の辞書には無いので空として読む
+    #[serde(default)]
+    tags: Vec<String>,
+    extra: X,
+}
+
+impl DictionaryWord {
+    pub fn new(key: String, ruby: String, description: String) -> Self {
+        Self::new_all(
+            vec![if !ruby.is_empty() {
+                DictionaryWordKeyPhrase::new_ruby(key, ruby)
+            } else {
+                DictionaryWordKeyPhrase::new_plain(key)
+            }],
+            description,
+            (),
+        )
+    }
+}
+
+impl<X> DictionaryWord<X> {
+    pub fn new_all(phrase: Vec<DictionaryWordKeyPhrase>, description: String, extra: X) -> Self {
+        Self {
+            key: phrase
+                .iter()
+                .map(|rp| match rp {
+                    DictionaryWordKeyPhrase::Plain { target } => target.as_str(),
+                    DictionaryWordKeyPhrase::Ruby { target, ruby: _ } => target.as_str(),
+                })
+                .collect(),
+            phrase,
+            description,
+            tags: vec![],
+            extra,
+        }
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// ルビの付いた部分はルビを、それ以外は表記をそのまま繋げた読み
+    pub fn reading(&self) -> String {
+        self.phrase
+            .iter()
+            .map(|rp| match rp {
+                DictionaryWordKeyPhrase::Plain { target } => target.as_str(),
+                DictionaryWordKeyPhrase::Ruby { target: _, ruby } => ruby.as_str(),
+            })
+            .collect()
+    }
+}
+
+/// [`PreparedDictionary`]の版が現在の版と異なるときの扱い
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum FormatVersionPolicy {
+    /// 語からトライを作り直す
+    #[default]
+    Rebuild,
+    /// [`Error::FormatVersionMismatch`]にする
+    Error,
+    /// 既知の旧版であれば、保存されたトライを語と突き合わせて検証した上で使う
+    Migrate,
+}
+
+/// 語と、[`DictionaryBackend`]が書き出したバイト列をまとめて保存できるようにした辞書
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "WD: Serialize", deserialize = "WD: Deserialize<'de>"))]
+pub struct PreparedDictionary<WD, B = Trie>
+where
+    WD: Clone + DictionaryWordContainer,
+{
+    format_version: String,
+    pub(crate) words: Vec<WD>,
+    pub(crate) trie_vec: Vec<u8>,
+    #[serde(default)]
+    pub(crate) reading_index: ReadingIndex,
+    #[serde(default)]
+    checksum: Option<u32>,
+    #[serde(skip)]
+    backend: PhantomData<fn() -> B>,
+}
+
+// `B`そのものは持たないので、`B`に境界を付けずに実装する
+impl<WD, B> Clone for PreparedDictionary<WD, B>
+where
+    WD: Clone + DictionaryWordContainer,
+{
+    fn clone(&self) -> Self {
+        Self {
+            format_version: self.format_version.clone(),
+            words: self.words.clone(),
+            trie_vec: self.trie_vec.clone(),
+            reading_index: self.reading_index.clone(),
+            checksum: self.checksum,
+            backend: PhantomData,
+        }
+    }
+}
+
+impl<WD, B> PartialEq for PreparedDictionary<WD, B>
+where
+    WD: Clone + DictionaryWordContainer + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.format_version == other.format_version
+            && self.words == other.words
+            && self.trie_vec == other.trie_vec
+            && self.reading_index == other.reading_index
+            && self.checksum == other.checksum
+    }
+}
+
+impl<WD, B> Debug for PreparedDictionary<WD, B>
+where
+    WD: Clone + DictionaryWordContainer + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreparedDictionary")
+            .field("format_version", &self.format_version)
+            .field("words", &self.words)
+            .field("trie_vec", &self.trie_vec)
+            .field("reading_index", &self.reading_index)
+            .field("checksum", &self.checksum)
+            .finish()
+    }
+}
+
+impl<WD> PreparedDictionary<WD>
+where
+    WD: Clone + DictionaryWordContainer,
+    WD::Extra: Serialize,
+{
+    pub fn prepare(words: Vec<WD>) -> Result<Self> {
+        Self::prepare_with_backend(words)
+    }
+
+    /// 語を集めてから[`PreparedDictionary::prepare`]する。集めた並びをそのまま持つので、
+    /// 語を1つずつ詰める[`crate::MappedDictionaryBuilder`]と違い、語の並び全体がメモリに載る
+    pub fn prepare_iter(words: impl IntoIterator<Item = WD>) -> Result<Self> {
+        Self::prepare_with_backend(words.into_iter().collect())
+    }
+}
+
+impl<WD, B> PreparedDictionary<WD, B>
+where
+    WD: Clone + DictionaryWordContainer,
+    WD::Extra: Serialize,
+    B: DictionaryBackend,
+{
+    /// 1.1.0で語のタグ、読みの索引、チェックサムを加えた
+    pub(crate) const CURRENT_FORMAT_VERSION: &str = "1.1.0";
+    /// トライの形式が現在と同じで、移行できる旧版
+    const MIGRATABLE_FORMAT_VERSIONS: [&str; 1] = ["1.0.0"];
+
+    pub fn format_version(&self) -> &str {
+        &self.format_version
+    }
+
+    /// [`PreparedDictionary::prepare`]の、見出しを引く実装を選べるもの
+    pub fn prepare_with_backend(words: Vec<WD>) -> Result<Self> {
+        if words.is_empty() {
+            return Err(Error::SerializeDictionary);
+        }
+        let trie_vec = B::build(&words)?.serialize();
+        let reading_index = ReadingIndex::build(&words);
+        let checksum = Some(checksum(&words, &trie_vec, &reading_index)?);
+        Ok(Self {
+            format_version: Self::CURRENT_FORMAT_VERSION.into(),
+            words,
+            trie_vec,
+            reading_index,
+            checksum,
+            backend: PhantomData,
+        })
+    }
+
+    /// 現在の版であること、チェックサムが一致すること、トライが語と矛盾しないことを確かめる
+    pub fn verify(&self) -> Result<()> {
+        if self.format_version != Self::CURRENT_FORMAT_VERSION {
+            return Err(self.version_mismatch());
+        }
+        if self.checksum != Some(checksum(&self.words, &self.trie_vec, &self.reading_index)?) {
+            return Err(Error::CorruptedDictionary("チェックサムが一致しません"));
+        }
+        if !self.reading_index.is_valid(self.words.len()) {
+            return Err(Error::CorruptedDictionary("読みの索引が壊れています"));
+        }
+        self.verify_trie()
+    }
+
+    /// 旧版の辞書を、トライを作り直さずに現在の版へ移行する。旧版でもチェックサムがあれば確かめる
+    pub fn migrate(mut self) -> Result<Self> {
+        if self.format_version == Self::CURRENT_FORMAT_VERSION {
+            self.verify()?;
+        } else if Self::MIGRATABLE_FORMAT_VERSIONS.contains(&self.format_version.as_str()) {
+            if self.checksum.is_some()
+                && self.checksum
+                    != Some(checksum(&self.words, &self.trie_vec, &self.reading_index)?)
+            {
+                return Err(Error::CorruptedDictionary("チェックサムが一致しません"));
+            }
+            self.verify_trie()?;
+            self.format_version = Self::CURRENT_FORMAT_VERSION.into();
+            self.reading_index = ReadingIndex::build(&self.words);
+            self.checksum = Some(checksum(&self.words, &self.trie_vec, &self.reading_index)?);
+        } else {
+            return Err(self.version_mismatch());
+        }
+        Ok(self)
+    }
+
+    fn verify_trie(&self) -> Result<()> {
+        B::verify(&self.trie_vec, &self.words)
+    }
+
+    pub(crate) fn version_mismatch(&self) -> Error {
+        Error::new_format_version_mismatch(
+            Self::CURRENT_FORMAT_VERSION,
+            self.format_version.clone(),
+        )
+    }
+}
+
+/// 語の内容と付加情報、トライのバイト列、読みの索引にわたるCRC32。長さも含めて区切りを曖昧にしない
+fn checksum<WD>(words: &[WD], trie_vec: &[u8], reading_index: &ReadingIndex) -> Result<u32>
+where
+    WD: DictionaryWordContainer,
+    WD::Extra: Serialize,
+{
+    fn update_bytes(hasher: &mut crc32fast::Hasher, bytes: &[u8]) {
+        hasher.update(&(bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    }
+    let mut hasher = crc32fast::Hasher::new();
+    update_bytes(&mut hasher, trie_vec);
+    for word in words.iter().map(|w| w.word()) {
+        update_bytes(&mut hasher, word.key().as_bytes());
+        for phrase in word.phrase() {
+            match phrase {
+                DictionaryWordKeyPhrase::Plain { target } => {
+                    hasher.update(&[0]);
+                    update_bytes(&mut hasher, target.as_bytes());
+                }
+                DictionaryWordKeyPhrase::Ruby { target, ruby } => {
+                    hasher.update(&[1]);
+                    update_bytes(&mut hasher, target.as_bytes());
+                    update_bytes(&mut hasher, ruby.as_bytes());
+                }
+            }
+        }
+        update_bytes(&mut hasher, word.description().as_bytes());
+        for tag in word.tags() {
+            update_bytes(&mut hasher, tag.as_bytes());
+        }
+        let extra =
+            postcard::to_allocvec(word.extra()).map_err(Error::new_serialize_dictionary_extra)?;
+        update_bytes(&mut hasher, &extra);
+    }
+    for (reading, i) in reading_index.entries() {
+        update_bytes(&mut hasher, reading.as_bytes());
+        hasher.update(&i.to_le_bytes());
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::parse_dictionary::DoubleArrayDictionary;
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn words() -> Vec<DictionaryWord> {
+        vec![
+        DictionaryWord::new_all(vec![
+            DictionaryWordKeyPhrase::new_ruby("若々".into(),"わかわか".into()),
+            DictionaryWordKeyPhrase::new_plain("しい".into()),
+        ], "年齢のわりに若く見えること、また、活力や気力が衰えていないこと".into(),()),
+        DictionaryWord::new("茶屋".into(), "ちゃや".into(), "製した茶を売る店。茶舗。".into()),
+        DictionaryWord::new("自分".into(), "じぶん".into(), "その人自身。".into()),
+        DictionaryWord::new("好奇心".into(), "こうきしん".into(), "新しいことや未知のこと、珍しい物事に対して強い興味や関心を持ち、それを知りたい、体験したいと思う心".into()),
+        DictionaryWord::new(
+            "旅館".into(),
+            "りょかん".into(),
+            "旅行者を泊めることを業とする家。やどや。".into(),
+        ),
+        DictionaryWord::new(
+            "避暑地".into(),
+            "ひしょち".into(),
+            "夏の暑さを避けるために訪れる、涼しい気候の土地".into(),
+        ),
+        DictionaryWord::new("留守".into(), "るす".into(), "外出して家にいないこと。".into()),
+        DictionaryWord::new(
+            "どうして".into(),
+            "".into(),
+            "どのようなわけで。なぜ。".into(),
+        ),
+        DictionaryWord::new("様式".into(), "ようしき".into(), "ある範囲の事物・しかたに共通に認められる、一定のありかた。".into()),
+        DictionaryWord::new("眼".into(), "め".into(), "物を見るはたらきをする器官。め。".into()),
+        DictionaryWord::new("墓参り".into(), "はかまい".into(), "（自分の家や縁故者の）墓にお参りすること。".into()),
+        DictionaryWord::new("交際".into(), "こうさい".into(), "人間（国）どうしがつきあうこと。つきあい。".into()),
+        DictionaryWord::new("問答".into(), "もんどう".into(), "問うことと答えること。また、問い答えのやりとり。".into()),
+    ]
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into()), "ちゃや")]
+    #[case(DictionaryWord::new("どうして".into(), "".into(), "".into()), "どうして")]
+    #[case(DictionaryWord::new_all(vec![
+        DictionaryWordKeyPhrase::new_ruby("若々".into(),"わかわか".into()),
+        DictionaryWordKeyPhrase::new_plain("しい".into()),
+    ], "".into(), ()), "わかわかしい")]
+    fn dictionary_word_reading_works(#[case] word: DictionaryWord, #[case] expected: &str) {
+        assert_that!(word.reading(), eq(expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(words())]
+    fn prepared_dictionary_prepare_works(#[case] words: Vec<DictionaryWord>) -> anyhow::Result<()> {
+        let pd = PreparedDictionary::prepare(words.clone())?;
+        assert_that!(pd.words, eq(&words));
+
+        let serialized_data = serde_cbor::to_vec(&pd)?;
+        let de_pd = serde_cbor::from_slice::<PreparedDictionary<DictionaryWord>>(&serialized_data)?;
+        assert_that!(de_pd, eq(&pd));
+        Ok(())
+    }
+
+    #[gtest]
+    fn prepared_dictionary_prepare_iter_works() -> anyhow::Result<()> {
+        let pd = PreparedDictionary::prepare_iter(words())?;
+        assert_that!(pd, eq(&PreparedDictionary::prepare(words())?));
+        Ok(())
+    }
+
+    #[gtest]
+    fn prepared_dictionary_verify_works() -> anyhow::Result<()> {
+        let pd = PreparedDictionary::prepare(words())?;
+        assert_that!(pd.verify(), ok(anything()));
+
+        let mut tampered = pd.clone();
+        tampered.words[0] = DictionaryWord::new("改竄".into(), "".into(), "".into());
+        assert_that!(
+            tampered.verify(),
+            err(matches_pattern!(Error::CorruptedDictionary(anything())))
+        );
+
+        let mut truncated = pd.clone();
+        truncated.trie_vec.truncate(truncated.trie_vec.len() / 2);
+        truncated.checksum = Some(checksum(
+            &truncated.words,
+            &truncated.trie_vec,
+            &truncated.reading_index,
+        )?);
+        assert_that!(
+            truncated.verify(),
+            err(matches_pattern!(Error::CorruptedDictionary(eq(
+                &"トライが壊れています"
+            ))))
+        );
+
+        let mut broken_index = pd.clone();
+        broken_index.reading_index = ReadingIndex::build(&words()[..1]);
+        broken_index.words.truncate(0);
+        broken_index.checksum = Some(checksum(
+            &broken_index.words,
+            &broken_index.trie_vec,
+            &broken_index.reading_index,
+        )?);
+        assert_that!(
+            broken_index.verify(),
+            err(matches_pattern!(Error::CorruptedDictionary(eq(
+                &"読みの索引が壊れています"
+            ))))
+        );
+
+        let mut old = pd.clone();
+        old.format_version = "0.9.0".into();
+        assert_that!(
+            old.verify(),
+            err(matches_pattern!(Error::FormatVersionMismatch(
+                eq(&"1.1.0"),
+                eq("0.9.0")
+            )))
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn verify_detects_tampered_extra() -> anyhow::Result<()> {
+        let word = |extra: u32| {
+            DictionaryWord::new_all(
+                vec![DictionaryWordKeyPhrase::new_plain("茶屋".into())],
+                "".into(),
+                extra,
+            )
+        };
+        let mut pd = PreparedDictionary::prepare(vec![word(1)])?;
+        pd.words[0] = word(2);
+        assert_that!(
+            pd.verify(),
+            err(matches_pattern!(Error::CorruptedDictionary(eq(
+                &"チェックサムが一致しません"
+            ))))
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn verify_rejects_trie_pointing_out_of_bounds() -> anyhow::Result<()> {
+        let mut pd = PreparedDictionary::prepare(words())?;
+        // 辞書にない文字の符号を字母の外に書き換える。語の経路は変わらないが、
+        // その文字を引くとcrawdadはノードの範囲外を読む
+        let alphabet_size_at = 4 + u32::from_le_bytes(pd.trie_vec[..4].try_into()?) as usize * 4;
+        let alphabet_size = u32::from_le_bytes(pd.trie_vec[alphabet_size_at..][..4].try_into()?);
+        let code_at = 4 + 'A' as usize * 4;
+        pd.trie_vec[code_at..code_at + 4].copy_from_slice(&(alphabet_size + 0xFFFF).to_le_bytes());
+        pd.checksum = Some(checksum(&pd.words, &pd.trie_vec, &pd.reading_index)?);
+
+        assert_that!(
+            pd.verify(),
+            err(matches_pattern!(Error::CorruptedDictionary(eq(
+                &"トライが範囲外を指しています"
+            ))))
+        );
+        assert_that!(
+            DoubleArrayDictionary::try_from(pd).map(|_| ()),
+            err(anything())
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn loaded_dictionary_uses_stored_reading_index() -> anyhow::Result<()> {
+        let mut pd = PreparedDictionary::prepare(words())?;
+        // 作り直せば出てこない索引を入れておき、読み込んだ辞書がそれを使うことを確かめる
+        let mut reversed = words();
+        reversed.reverse();
+        pd.reading_index = ReadingIndex::build(&reversed);
+        pd.checksum = Some(checksum(&pd.words, &pd.trie_vec, &pd.reading_index)?);
+
+        let dic = DoubleArrayDictionary::try_from(pd)?;
+        assert_that!(
+            dic.search_by_reading("ちゃや")
+                .map(|w| w.key())
+                .collect::<Vec<_>>(),
+            elements_are![eq(&reversed[1].key())]
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn loads_and_migrates_1_0_0_dictionary() -> anyhow::Result<()> {
+        // 1.0.0の形。語にタグが無く、読みの索引とチェックサムも無い
+        #[derive(Serialize)]
+        struct WordV1 {
+            key: String,
+            phrase: Vec<DictionaryWordKeyPhrase>,
+            description: String,
+            extra: (),
+        }
+        #[derive(Serialize)]
+        struct PreparedV1 {
+            format_version: String,
+            words: Vec<WordV1>,
+            trie_vec: Vec<u8>,
+        }
+        let v1 = PreparedV1 {
+            format_version: "1.0.0".into(),
+            words: words()
+                .into_iter()
+                .map(|w| WordV1 {
+                    key: w.key,
+                    phrase: w.phrase,
+                    description: w.description,
+                    extra: (),
+                })
+                .collect(),
+            trie_vec: Trie::build(&words())?.serialize(),
+        };
+
+        let pd: PreparedDictionary<DictionaryWord> =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&v1)?)?;
+        assert_that!(pd.format_version(), eq("1.0.0"));
+        assert_that!(pd.words, eq(&words()));
+        assert_that!(pd.migrate()?, eq(&PreparedDictionary::prepare(words())?));
+        Ok(())
+    }
+
+    #[gtest]
+    fn prepared_dictionary_migrate_works() -> anyhow::Result<()> {
+        let pd = PreparedDictionary::prepare(words())?;
+        let mut old = pd.clone();
+        old.format_version = "1.0.0".into();
+        old.reading_index = ReadingIndex::default();
+        old.checksum = None;
+        assert_that!(old.clone().migrate()?, eq(&pd));
+
+        let mut mismatched = old.clone();
+        mismatched.words.swap(0, 1);
+        assert_that!(
+            mismatched.migrate(),
+            err(matches_pattern!(Error::CorruptedDictionary(anything())))
+        );
+
+        // トライと語は合っていても、残っているチェックサムと食い違えば移行しない
+        let mut tampered = old.clone();
+        tampered.checksum = Some(checksum(&old.words, &old.trie_vec, &old.reading_index)?);
+        tampered.words[0] = tampered.words[0].clone().with_tags(vec!["改竄".into()]);
+        assert_that!(
+            tampered.migrate(),
+            err(matches_pattern!(Error::CorruptedDictionary(eq(
+                &"チェックサムが一致しません"
+            ))))
+        );
+
+        old.format_version = "0.9.0".into();
+        assert_that!(
+            old.migrate(),
+            err(matches_pattern!(Error::FormatVersionMismatch(
+                anything(),
+                anything()
+            )))
+        );
+        Ok(())
+    }
+}
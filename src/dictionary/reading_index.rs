@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{dictionary::kana::to_hiragana, general_parser::DictionaryWordContainer};
+
+/// 読みから語を引くための索引。ルビの付いた語の読みを平仮名に揃え、読み順に並べて持つ
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug, Default)]
+pub(crate) struct ReadingIndex {
+    entries: Vec<(String, u32)>,
+}
+
+impl ReadingIndex {
+    pub(crate) fn build<WD>(words: &[WD]) -> Self
+    where
+        WD: DictionaryWordContainer,
+    {
+        let mut entries = words
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| {
+                w.word()
+                    .phrase()
+                    .iter()
+                    .any(|p| matches!(p, crate::DictionaryWordKeyPhrase::Ruby { .. }))
+            })
+            .map(|(i, w)| (fold(&w.word().reading()), i as u32))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        Self { entries }
+    }
+
+    pub(crate) fn entries(&self) -> &[(String, u32)] {
+        &self.entries
+    }
+
+    /// 読みが一致する語の番号
+    pub(crate) fn exact(&self, reading: &str) -> impl Iterator<Item = usize> {
+        let reading = fold(reading);
+        self.range(&reading)
+            .take_while(move |(r, _)| *r == reading)
+            .map(|(_, i)| *i as usize)
+    }
+
+    /// 読みが`prefix`で始まる語の番号
+    pub(crate) fn predictive(&self, prefix: &str) -> impl Iterator<Item = usize> {
+        let prefix = fold(prefix);
+        self.range(&prefix)
+            .take_while(move |(r, _)| r.starts_with(&prefix))
+            .map(|(_, i)| *i as usize)
+    }
+
+    fn range(&self, reading: &str) -> std::slice::Iter<'_, (String, u32)> {
+        let start = self.entries.partition_point(|(r, _)| r.as_str() < reading);
+        self.entries[start..].iter()
+    }
+
+    /// 語の数に収まり、読み順に並んでいるか
+    pub(crate) fn is_valid(&self, words_len: usize) -> bool {
+        self.entries.iter().all(|(_, i)| (*i as usize) < words_len) && self.entries.is_sorted()
+    }
+}
+
+/// 片仮名を平仮名に揃える
+fn fold(reading: &str) -> String {
+    to_hiragana(reading)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DictionaryWord, DictionaryWordKeyPhrase};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn words() -> Vec<DictionaryWord> {
+        vec![
+            DictionaryWord::new("大砲".into(), "たいほう".into(), "".into()),
+            DictionaryWord::new("魔導".into(), "マドウ".into(), "".into()),
+            DictionaryWord::new_all(
+                vec![
+                    DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                    DictionaryWordKeyPhrase::new_plain("しい".into()),
+                ],
+                "".into(),
+                (),
+            ),
+            DictionaryWord::new("どうして".into(), "".into(), "".into()),
+            DictionaryWord::new("退歩".into(), "たいほ".into(), "".into()),
+        ]
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("たいほう", vec![0])]
+    #[case("タイホウ", vec![0])]
+    #[case("まどう", vec![1])]
+    #[case("わかわかしい", vec![2])]
+    #[case("わかわか", vec![])]
+    #[case("どうして", vec![])]
+    fn exact_works(#[case] reading: &str, #[case] expected: Vec<usize>) {
+        let index = ReadingIndex::build(&words());
+        assert_that!(index.exact(reading).collect::<Vec<_>>(), eq(&expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("たいほ", vec![4, 0])]
+    #[case("タイ", vec![4, 0])]
+    #[case("わか", vec![2])]
+    #[case("ん", vec![])]
+    fn predictive_works(#[case] prefix: &str, #[case] expected: Vec<usize>) {
+        let index = ReadingIndex::build(&words());
+        assert_that!(index.predictive(prefix).collect::<Vec<_>>(), eq(&expected));
+    }
+}
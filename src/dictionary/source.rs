@@ -0,0 +1,211 @@
+//! 人が書いて直せる辞書のTOML形式。見出しは本文と同じルビ記法で書く
+//!
+//! ```toml
+//! [[word]]
+//! key = "|若々《わかわか》しい"
+//! description = "年齢のわりに若く見えること"
+//! tags = ["形容詞"]
+//! ```
+
+use std::{fs, path::Path};
+
+use nom::{Parser, branch::alt};
+use serde::{Deserialize, de::DeserializeOwned};
+use toml::Spanned;
+
+use crate::{
+    DictionaryWord, DictionaryWordKeyPhrase, Error, Phrase, Result, RubyLimits,
+    parser::nom_parsers::{kanji_ruby, ruby_instruction},
+};
+
+#[derive(Deserialize)]
+#[serde(bound = "X: DeserializeOwned + Default")]
+struct DictionarySource<X> {
+    #[serde(default, rename = "word")]
+    words: Vec<DictionarySourceEntry<X>>,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "X: DeserializeOwned + Default")]
+struct DictionarySourceEntry<X> {
+    key: Spanned<String>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    extra: X,
+}
+
+pub fn read_dictionary_toml<X>(path: impl AsRef<Path>) -> Result<Vec<DictionaryWord<X>>>
+where
+    X: DeserializeOwned + Default,
+{
+    let source = fs::read_to_string(path).map_err(Error::new_read_dictionary)?;
+    parse_dictionary_toml(&source)
+}
+
+/// TOML形式の辞書を読む。誤りは行番号(1始まり)付きの[`Error::InvalidDictionarySource`]になる
+pub fn parse_dictionary_toml<X>(source: &str) -> Result<Vec<DictionaryWord<X>>>
+where
+    X: DeserializeOwned + Default,
+{
+    let dictionary = toml::from_str::<DictionarySource<X>>(source).map_err(|e| {
+        Error::new_invalid_dictionary_source(
+            e.span().map_or(1, |span| line_of(source, span.start)),
+            e.message().into(),
+        )
+    })?;
+    dictionary
+        .words
+        .into_iter()
+        .map(|entry| {
+            let phrase = parse_key(entry.key.get_ref()).map_err(|message| {
+                Error::new_invalid_dictionary_source(
+                    line_of(source, entry.key.span().start),
+                    message.into(),
+                )
+            })?;
+            Ok(
+                DictionaryWord::new_all(phrase, entry.description, entry.extra)
+                    .with_tags(entry.tags),
+            )
+        })
+        .collect()
+}
+
+/// 本文と同じ記法で書かれた見出しを、ルビの付いた部分とそれ以外に分ける
+fn parse_key(key: &str) -> std::result::Result<Vec<DictionaryWordKeyPhrase>, &'static str> {
+    let mut phrase = vec![];
+    let mut plain = String::new();
+    let mut rest = key;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            return Err("見出しに空白や改行は使えません");
+        }
+        let limits = RubyLimits::unlimited();
+        let parsed = alt((
+            |i| ruby_instruction::<&str, ()>(i, &limits),
+            |i| kanji_ruby(i, &limits),
+        ))
+        .parse(rest);
+        match parsed
+            .as_ref()
+            .map(|(next, fragment)| (*next, fragment.phrase()))
+        {
+            Ok((next, Phrase::Ruby(ruby))) => {
+                if ruby.ruby().is_empty() {
+                    return Err("ルビが空です");
+                }
+                if !plain.is_empty() {
+                    phrase.push(DictionaryWordKeyPhrase::new_plain(std::mem::take(
+                        &mut plain,
+                    )));
+                }
+                phrase.push(DictionaryWordKeyPhrase::new_ruby(
+                    ruby.target().to_string(),
+                    ruby.ruby().to_string(),
+                ));
+                rest = next;
+            }
+            _ => {
+                plain.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !plain.is_empty() {
+        phrase.push(DictionaryWordKeyPhrase::new_plain(plain));
+    }
+    if phrase.is_empty() {
+        return Err("見出しが空です");
+    }
+    Ok(phrase)
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    #[rstest]
+    #[case("|若々《わかわか》しい", vec![
+        DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+        DictionaryWordKeyPhrase::new_plain("しい".into()),
+    ])]
+    #[case("お茶屋《ちゃや》", vec![
+        DictionaryWordKeyPhrase::new_plain("お".into()),
+        DictionaryWordKeyPhrase::new_ruby("茶屋".into(), "ちゃや".into()),
+    ])]
+    #[case("どうして", vec![DictionaryWordKeyPhrase::new_plain("どうして".into())])]
+    fn parse_key_works(#[case] key: &str, #[case] expected: Vec<DictionaryWordKeyPhrase>) {
+        assert_that!(parse_key(key), ok(eq(&expected)));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("", "見出しが空です")]
+    #[case("茶屋《》", "ルビが空です")]
+    #[case("お 茶", "見出しに空白や改行は使えません")]
+    fn parse_key_rejects(#[case] key: &str, #[case] expected: &str) {
+        assert_that!(parse_key(key), err(eq(&expected)));
+    }
+
+    #[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+    struct Extra {
+        priority: u32,
+    }
+
+    #[gtest]
+    fn parse_dictionary_toml_works() -> anyhow::Result<()> {
+        let words = parse_dictionary_toml::<Extra>(include_str!("test_data/source/words.toml"))?;
+        assert_that!(
+            words,
+            eq(&vec![
+                DictionaryWord::new_all(
+                    vec![
+                        DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                        DictionaryWordKeyPhrase::new_plain("しい".into()),
+                    ],
+                    "年齢のわりに若く見えること".into(),
+                    Extra { priority: 2 },
+                )
+                .with_tags(vec!["形容詞".into()]),
+                DictionaryWord::new_all(
+                    vec![DictionaryWordKeyPhrase::new_ruby(
+                        "茶屋".into(),
+                        "ちゃや".into()
+                    )],
+                    "|製茶《せいちゃ》を売る店。".into(),
+                    Extra::default(),
+                ),
+            ])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("[[word]]\ndescription = \"x\"\n", 1)]
+    #[case("[[word]]\nkey = \"茶屋\"\n\n[[word]]\nkey = \"茶屋《》\"\n", 5)]
+    #[case("[[word]]\nkey = \"茶屋\"\n\n[[word]]\nkey = \"魔導\"\ntags = 1\n", 6)]
+    #[case("[[word]]\nkey = \"茶屋\"\ndescription = \"閉じていない\n", 3)]
+    fn parse_dictionary_toml_reports_line(#[case] source: &str, #[case] expected_line: usize) {
+        let result = parse_dictionary_toml::<()>(source);
+        assert_that!(
+            result,
+            err(matches_pattern!(Error::InvalidDictionarySource(
+                eq(&expected_line),
+                anything()
+            )))
+        );
+    }
+}
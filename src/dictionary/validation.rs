@@ -0,0 +1,231 @@
+use std::{collections::HashMap, fmt::Display};
+
+use derive_getters::Getters;
+use derive_new::new;
+
+use crate::{
+    DictionaryWord, DictionaryWordKeyPhrase,
+    parser::{
+        Error,
+        general_parser::DictionaryWordContainer,
+        nom_parsers::char::{is_new_line_escape, is_space, is_tab, is_zenkaku_space},
+    },
+};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DictionaryIssueKind {
+    /// 見出しが空
+    EmptyKey,
+    /// 見出しが`first`番目の語と重複している
+    DuplicateKey { first: usize },
+    /// 見出しが空白(半角、全角)、タブ、改行で始まり、本文と一致することがない(解析では空白や改行を辞書より先に区切る)
+    UnmatchableKey,
+    /// `segment`番目のルビの付いた部分で、表記かルビが空
+    EmptyRuby { segment: usize },
+    /// 見出しで始まるより長い語があり、その語が現れる箇所では一致しない
+    ShadowedKey { by: Vec<usize> },
+}
+
+/// 辞書の語の問題。`index`は渡した語の並びでの位置
+#[derive(new, Getters, Clone, PartialEq, Debug)]
+pub struct DictionaryIssue {
+    index: usize,
+    kind: DictionaryIssueKind,
+}
+
+impl DictionaryIssue {
+    /// 辞書を作れなくなる、または語が使われなくなる問題か。`false`なら注意に留まる
+    pub fn is_error(&self) -> bool {
+        !matches!(self.kind, DictionaryIssueKind::ShadowedKey { .. })
+    }
+}
+
+impl Display for DictionaryIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}番目の語: ", self.index)?;
+        match &self.kind {
+            DictionaryIssueKind::EmptyKey => f.write_str("見出しが空です"),
+            DictionaryIssueKind::DuplicateKey { first } => {
+                write!(f, "見出しが{first}番目の語と重複しています")
+            }
+            DictionaryIssueKind::UnmatchableKey => {
+                f.write_str("見出しが空白や改行で始まるため一致しません")
+            }
+            DictionaryIssueKind::EmptyRuby { segment } => {
+                write!(f, "{segment}番目の部分のルビが空です")
+            }
+            DictionaryIssueKind::ShadowedKey { by } => {
+                write!(f, "より長い語({by:?}番目)に隠れることがあります")
+            }
+        }
+    }
+}
+
+/// 辞書の語の問題をすべて、語の順に返す
+pub fn validate_dictionary<X>(words: &[DictionaryWord<X>]) -> Vec<DictionaryIssue> {
+    validate_words(words)
+}
+
+pub(crate) fn validate_words<WD>(words: &[WD]) -> Vec<DictionaryIssue>
+where
+    WD: DictionaryWordContainer,
+{
+    let mut issues = vec![];
+    let mut first_indices: HashMap<&str, usize> = HashMap::new();
+    for (index, word) in words.iter().map(|w| w.word()).enumerate() {
+        let key = word.key().as_str();
+        if key.is_empty() {
+            issues.push(DictionaryIssue::new(index, DictionaryIssueKind::EmptyKey));
+            continue;
+        }
+        if let Some(&first) = first_indices.get(key) {
+            issues.push(DictionaryIssue::new(
+                index,
+                DictionaryIssueKind::DuplicateKey { first },
+            ));
+        } else {
+            first_indices.insert(key, index);
+        }
+        if key.starts_with(|c| {
+            is_space(c) || is_zenkaku_space(c) || is_tab(c) || is_new_line_escape(c)
+        }) {
+            issues.push(DictionaryIssue::new(
+                index,
+                DictionaryIssueKind::UnmatchableKey,
+            ));
+        }
+        for (segment, phrase) in word.phrase().iter().enumerate() {
+            if let DictionaryWordKeyPhrase::Ruby { target, ruby } = phrase
+                && (target.is_empty() || ruby.is_empty())
+            {
+                issues.push(DictionaryIssue::new(
+                    index,
+                    DictionaryIssueKind::EmptyRuby { segment },
+                ));
+            }
+        }
+    }
+
+    // 見出し順に並べ、直後に続く自身で始まる見出しを集める
+    let mut sorted = first_indices.into_iter().collect::<Vec<_>>();
+    sorted.sort_unstable();
+    for (i, (key, index)) in sorted.iter().enumerate() {
+        let by = sorted[i + 1..]
+            .iter()
+            .take_while(|(longer, _)| longer.starts_with(key))
+            .map(|(_, index)| *index)
+            .collect::<Vec<_>>();
+        if !by.is_empty() {
+            issues.push(DictionaryIssue::new(
+                *index,
+                DictionaryIssueKind::ShadowedKey { by },
+            ));
+        }
+    }
+    issues.sort_by_key(|issue| issue.index);
+    issues
+}
+
+/// トライを作れなかったとき、原因となった語が分かればそれを示すエラーにする
+pub(crate) fn explain_build_error<WD>(words: &[WD], error: Error) -> Error
+where
+    WD: DictionaryWordContainer,
+{
+    let issues = validate_words(words)
+        .into_iter()
+        .filter(DictionaryIssue::is_error)
+        .collect::<Vec<_>>();
+    if issues.is_empty() {
+        error
+    } else {
+        Error::InvalidDictionaryWords(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ParseText;
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    fn validate_dictionary_works() {
+        let words = vec![
+            DictionaryWord::new("炎".into(), "ほのお".into(), "".into()),
+            DictionaryWord::new("".into(), "".into(), "".into()),
+            DictionaryWord::new("炎炎".into(), "えんえん".into(), "".into()),
+            DictionaryWord::new("炎".into(), "えん".into(), "".into()),
+            DictionaryWord::new(" 大砲".into(), "".into(), "".into()),
+            DictionaryWord::new_all(
+                vec![
+                    DictionaryWordKeyPhrase::new_ruby("若々".into(), "".into()),
+                    DictionaryWordKeyPhrase::new_plain("しい".into()),
+                ],
+                "".into(),
+                (),
+            ),
+            DictionaryWord::new("炎上".into(), "えんじょう".into(), "".into()),
+        ];
+        assert_that!(
+            validate_dictionary(&words),
+            eq(&vec![
+                DictionaryIssue::new(0, DictionaryIssueKind::ShadowedKey { by: vec![6, 2] }),
+                DictionaryIssue::new(1, DictionaryIssueKind::EmptyKey),
+                DictionaryIssue::new(3, DictionaryIssueKind::DuplicateKey { first: 0 }),
+                DictionaryIssue::new(4, DictionaryIssueKind::UnmatchableKey),
+                DictionaryIssue::new(5, DictionaryIssueKind::EmptyRuby { segment: 0 }),
+            ])
+        );
+    }
+
+    #[gtest]
+    fn build_error_names_words() {
+        let result = crate::Parser::try_new_with_dic(vec![
+            DictionaryWord::new("炎".into(), "".into(), "".into()),
+            DictionaryWord::new("炎".into(), "".into(), "".into()),
+        ]);
+        assert_that!(
+            result.err(),
+            some(matches_pattern!(Error::InvalidDictionaryWords(eq(&vec![
+                DictionaryIssue::new(1, DictionaryIssueKind::DuplicateKey { first: 0 })
+            ]))))
+        );
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(" 大砲", false)]
+    #[case("大 砲", true)]
+    #[case("　大砲", false)]
+    #[case("\t大砲", false)]
+    #[case("\n大砲", false)]
+    #[case("\u{00A0}大砲", true)]
+    fn only_leading_white_space_is_unmatchable(
+        #[case] key: &str,
+        #[case] expected: bool,
+    ) -> anyhow::Result<()> {
+        let words = vec![DictionaryWord::new(key.into(), "".into(), "".into())];
+        let unmatchable = validate_dictionary(&words)
+            .iter()
+            .any(|issue| issue.kind() == &DictionaryIssueKind::UnmatchableKey);
+        assert_that!(unmatchable, eq(!expected));
+        let parser = crate::Parser::try_new_with_dic(words)?;
+        assert_that!(
+            parser
+                .parse_iter(key)
+                .any(|f| matches!(f.phrase(), crate::Phrase::DictionaryWord(_))),
+            eq(expected)
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn display_works() {
+        assert_that!(
+            DictionaryIssue::new(3, DictionaryIssueKind::DuplicateKey { first: 0 }).to_string(),
+            eq("3番目の語: 見出しが0番目の語と重複しています")
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+};
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zip::ZipArchive;
+
+use crate::{DictionaryWord, DictionaryWordKeyPhrase, Error, Result, dictionary::kana::is_kana};
+
+#[derive(Getters, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct YomitanTermMeta {
+    score: i64,
+    sequence: i64,
+    rules: Vec<String>,
+}
+
+impl From<YomitanTermMeta> for () {
+    fn from(_: YomitanTermMeta) -> Self {}
+}
+
+pub fn import_yomitan_archive<X>(path: impl AsRef<Path>) -> Result<Vec<DictionaryWord<X>>>
+where
+    X: From<YomitanTermMeta>,
+{
+    let file = File::open(path).map_err(Error::new_read_dictionary)?;
+    import_yomitan_archive_from_reader(BufReader::new(file))
+}
+
+pub fn import_yomitan_archive_from_reader<R, X>(reader: R) -> Result<Vec<DictionaryWord<X>>>
+where
+    R: Read + Seek,
+    X: From<YomitanTermMeta>,
+{
+    let mut archive = ZipArchive::new(reader).map_err(Error::new_read_yomitan_archive)?;
+    let mut bank_names = archive
+        .file_names()
+        .filter(|name| is_term_bank(name))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    bank_names.sort_by_key(|name| term_bank_number(name));
+
+    let mut terms: Vec<YomitanTerm> = vec![];
+    let mut term_indices: HashMap<(String, String), usize> = HashMap::new();
+    for name in bank_names {
+        let mut json = String::new();
+        archive
+            .by_name(&name)
+            .map_err(Error::new_read_yomitan_archive)?
+            .read_to_string(&mut json)
+            .map_err(Error::new_read_dictionary)?;
+        let rows = serde_json::from_str::<Vec<Value>>(&json)
+            .map_err(Error::new_parse_yomitan_dictionary)?;
+        for (i, row) in rows.iter().enumerate() {
+            let term = YomitanTerm::from_row(row)
+                .ok_or_else(|| Error::new_invalid_yomitan_term(format!("{name}[{i}]")))?;
+            let key = (term.term.clone(), term.reading.clone());
+            if let Some(&same) = term_indices.get(&key) {
+                terms[same].merge(term);
+            } else {
+                term_indices.insert(key, terms.len());
+                terms.push(term);
+            }
+        }
+    }
+    Ok(terms.into_iter().map(YomitanTerm::into_word).collect())
+}
+
+fn is_term_bank(name: &str) -> bool {
+    name.starts_with("term_bank_") && name.ends_with(".json")
+}
+
+fn term_bank_number(name: &str) -> usize {
+    name.trim_start_matches("term_bank_")
+        .trim_end_matches(".json")
+        .parse()
+        .unwrap_or(usize::MAX)
+}
+
+struct YomitanTerm {
+    term: String,
+    reading: String,
+    glossary: Vec<String>,
+    tags: Vec<String>,
+    meta: YomitanTermMeta,
+}
+
+impl YomitanTerm {
+    // v3: [term, reading, definitionTags, rules, score, glossary, sequence, termTags]
+    // v1: [term, reading, definitionTags, rules, score, ...glossary]
+    fn from_row(row: &Value) -> Option<Self> {
+        let row = row.as_array()?;
+        let term = row.first()?.as_str()?.to_string();
+        if term.is_empty() {
+            return None;
+        }
+        let reading = row.get(1)?.as_str()?.to_string();
+        let mut tags = split_tags(row.get(2));
+        let rules = split_tags(row.get(3));
+        let score = row.get(4).and_then(Value::as_i64).unwrap_or_default();
+        let (glossary, sequence) = if let Some(Value::Array(glossary)) = row.get(5) {
+            tags.extend(split_tags(row.get(7)));
+            (
+                glossary.iter().filter_map(glossary_text).collect(),
+                row.get(6).and_then(Value::as_i64).unwrap_or_default(),
+            )
+        } else {
+            (row.iter().skip(5).filter_map(glossary_text).collect(), 0)
+        };
+        Some(Self {
+            term,
+            reading,
+            glossary,
+            tags,
+            meta: YomitanTermMeta {
+                score,
+                sequence,
+                rules,
+            },
+        })
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.glossary.extend(other.glossary);
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self.meta.score = self.meta.score.max(other.meta.score);
+    }
+
+    fn into_word<X>(self) -> DictionaryWord<X>
+    where
+        X: From<YomitanTermMeta>,
+    {
+        DictionaryWord::new_all(
+            key_phrase(self.term, self.reading),
+            self.glossary.join("\n"),
+            X::from(self.meta),
+        )
+        .with_tags(self.tags)
+    }
+}
+
+fn split_tags(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_str)
+        .map(|s| s.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// 送り仮名などの読みと共通する仮名を分離し、漢字部分にだけルビを振る
+fn key_phrase(term: String, reading: String) -> Vec<DictionaryWordKeyPhrase> {
+    if reading.is_empty() || reading == term {
+        return vec![DictionaryWordKeyPhrase::new_plain(term)];
+    }
+    let term_chars = term.chars().collect::<Vec<_>>();
+    let reading_chars = reading.chars().collect::<Vec<_>>();
+    let max = term_chars.len().min(reading_chars.len()) - 1;
+    let prefix = term_chars
+        .iter()
+        .zip(&reading_chars)
+        .take(max)
+        .take_while(|(t, r)| t == r && is_kana(**t))
+        .count();
+    let suffix = term_chars
+        .iter()
+        .rev()
+        .zip(reading_chars.iter().rev())
+        .take(max - prefix)
+        .take_while(|(t, r)| t == r && is_kana(**t))
+        .count();
+    let mut phrase = vec![];
+    if prefix > 0 {
+        phrase.push(DictionaryWordKeyPhrase::new_plain(
+            term_chars[..prefix].iter().collect(),
+        ));
+    }
+    phrase.push(DictionaryWordKeyPhrase::new_ruby(
+        term_chars[prefix..term_chars.len() - suffix]
+            .iter()
+            .collect(),
+        reading_chars[prefix..reading_chars.len() - suffix]
+            .iter()
+            .collect(),
+    ));
+    if suffix > 0 {
+        phrase.push(DictionaryWordKeyPhrase::new_plain(
+            term_chars[term_chars.len() - suffix..].iter().collect(),
+        ));
+    }
+    phrase
+}
+
+fn glossary_text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Object(o) => match o.get("type").and_then(Value::as_str) {
+            Some("text") => o.get("text")?.as_str()?.to_string(),
+            Some("structured-content") => {
+                let mut buf = String::new();
+                write_structured_content(&mut buf, o.get("content")?);
+                buf.trim().to_string()
+            }
+            Some("image") => o.get("description")?.as_str()?.to_string(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// structured-contentを平文に変換する。ルビはこのクレートのルビ記法で書き出す
+fn write_structured_content(buf: &mut String, content: &Value) {
+    match content {
+        Value::String(s) => buf.push_str(s),
+        Value::Array(contents) => {
+            for c in contents {
+                write_structured_content(buf, c);
+            }
+        }
+        Value::Object(o) => {
+            let content = o.get("content").unwrap_or(&Value::Null);
+            match o.get("tag").and_then(Value::as_str) {
+                Some("br") => buf.push('\n'),
+                Some("rt" | "rp" | "img") => {}
+                Some("ruby") => write_ruby(buf, content),
+                Some("div" | "p" | "li" | "tr" | "ol" | "ul" | "table" | "details" | "summary") => {
+                    push_line_break(buf);
+                    write_structured_content(buf, content);
+                    push_line_break(buf);
+                }
+                Some("td" | "th") => {
+                    write_structured_content(buf, content);
+                    buf.push(' ');
+                }
+                _ => write_structured_content(buf, content),
+            }
+        }
+        _ => {}
+    }
+}
+
+fn write_ruby(buf: &mut String, content: &Value) {
+    let mut target = String::new();
+    let mut ruby = String::new();
+    for c in content
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or(std::slice::from_ref(content))
+    {
+        match c.get("tag").and_then(Value::as_str) {
+            Some("rt") => write_structured_content(&mut ruby, c.get("content").unwrap_or(c)),
+            Some("rp") => {}
+            _ => write_structured_content(&mut target, c),
+        }
+    }
+    if ruby.is_empty() {
+        buf.push_str(&target);
+    } else {
+        buf.push('|');
+        buf.push_str(&target);
+        buf.push('《');
+        buf.push_str(&ruby);
+        buf.push('》');
+    }
+}
+
+fn push_line_break(buf: &mut String) {
+    if !buf.is_empty() && !buf.ends_with('\n') {
+        buf.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    #[rstest]
+    #[case("茶屋", "ちゃや", vec![DictionaryWordKeyPhrase::new_ruby("茶屋".into(), "ちゃや".into())])]
+    #[case("どうして", "どうして", vec![DictionaryWordKeyPhrase::new_plain("どうして".into())])]
+    #[case("どうして", "", vec![DictionaryWordKeyPhrase::new_plain("どうして".into())])]
+    #[case("若々しい", "わかわかしい", vec![
+        DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+        DictionaryWordKeyPhrase::new_plain("しい".into()),
+    ])]
+    #[case("お茶", "おちゃ", vec![
+        DictionaryWordKeyPhrase::new_plain("お".into()),
+        DictionaryWordKeyPhrase::new_ruby("茶".into(), "ちゃ".into()),
+    ])]
+    fn key_phrase_works(
+        #[case] term: &str,
+        #[case] reading: &str,
+        #[case] expected: Vec<DictionaryWordKeyPhrase>,
+    ) {
+        assert_that!(key_phrase(term.into(), reading.into()), eq(&expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(r#""その人自身。""#, Some("その人自身。"))]
+    #[case(r#"{"type":"text","text":"その人自身。"}"#, Some("その人自身。"))]
+    #[case(r#"{"type":"image","path":"a.png"}"#, None)]
+    #[case(
+        r#"{"type":"structured-content","content":[{"tag":"ul","content":[{"tag":"li","content":"一つ目"},{"tag":"li","content":"二つ目"}]}]}"#,
+        Some("一つ目\n二つ目")
+    )]
+    #[case(
+        r#"{"type":"structured-content","content":["製",{"tag":"span","content":"した"},{"tag":"br"},{"tag":"ruby","content":["茶",{"tag":"rp","content":"("},{"tag":"rt","content":"ちゃ"},{"tag":"rp","content":")"}]},"を売る店。"]}"#,
+        Some("製した\n|茶《ちゃ》を売る店。")
+    )]
+    fn glossary_text_works(#[case] json: &str, #[case] expected: Option<&str>) {
+        let value = serde_json::from_str::<Value>(json).unwrap();
+        assert_that!(glossary_text(&value).as_deref(), eq(expected));
+    }
+
+    #[gtest]
+    fn import_yomitan_archive_works() -> anyhow::Result<()> {
+        let words: Vec<DictionaryWord> =
+            import_yomitan_archive("src/dictionary/test_data/yomitan/v3.zip")?;
+        let expected = vec![
+            DictionaryWord::new(
+                "茶屋".into(),
+                "ちゃや".into(),
+                "製した茶を売る店。\n茶舗。".into(),
+            )
+            .with_tags(vec!["n".into(), "common".into()]),
+            DictionaryWord::new_all(
+                vec![
+                    DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                    DictionaryWordKeyPhrase::new_plain("しい".into()),
+                ],
+                "年齢のわりに若く見えること\n|活力《かつりょく》が衰えていないこと".into(),
+                (),
+            )
+            .with_tags(vec!["adj-i".into()]),
+            DictionaryWord::new(
+                "大砲".into(),
+                "たいほう".into(),
+                "砲弾を発射する兵器".into(),
+            ),
+        ];
+        assert_that!(words, eq(&expected));
+        Ok(())
+    }
+
+    #[gtest]
+    fn import_yomitan_archive_with_meta_works() -> anyhow::Result<()> {
+        let words: Vec<DictionaryWord<YomitanTermMeta>> =
+            import_yomitan_archive("src/dictionary/test_data/yomitan/v3.zip")?;
+        assert_that!(
+            words.iter().map(|w| w.extra().clone()).collect::<Vec<_>>(),
+            eq(&vec![
+                YomitanTermMeta {
+                    score: 10,
+                    sequence: 1,
+                    rules: vec![],
+                },
+                YomitanTermMeta {
+                    score: 5,
+                    sequence: 2,
+                    rules: vec!["adj-i".into()],
+                },
+                YomitanTermMeta {
+                    score: 0,
+                    sequence: 3,
+                    rules: vec![],
+                },
+            ])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn import_yomitan_archive_v1_works() -> anyhow::Result<()> {
+        let words: Vec<DictionaryWord> =
+            import_yomitan_archive("src/dictionary/test_data/yomitan/v1.zip")?;
+        let expected = vec![
+            DictionaryWord::new(
+                "自分".into(),
+                "じぶん".into(),
+                "その人自身。\n自己。".into(),
+            )
+            .with_tags(vec!["n".into()]),
+        ];
+        assert_that!(words, eq(&expected));
+        Ok(())
+    }
+
+    #[gtest]
+    fn import_yomitan_archive_invalid_term_fails() {
+        let data = std::fs::read("src/dictionary/test_data/yomitan/invalid.zip").unwrap();
+        let result = import_yomitan_archive_from_reader::<_, ()>(Cursor::new(data));
+        assert_that!(
+            result,
+            err(displays_as(contains_substring("term_bank_1.json[1]")))
+        );
+    }
+}
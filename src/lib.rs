@@ -1,7 +1,9 @@
 mod dictionary;
 mod parser;
 mod phrase;
+mod ruby_consistency;
 
 pub use dictionary::*;
 pub use parser::*;
 pub use phrase::*;
+pub use ruby_consistency::*;
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use nom::Input;
+
+use crate::{AnnotationPolicy, Phrase, PlainPhrase, SuppressedAnnotation, parser::ParsedFragment};
+
+/// 注釈の方針に従い、辞書に一致した語を注釈しないものに変える
+pub(crate) struct AnnotationIter<I> {
+    inner: I,
+    policy: AnnotationPolicy,
+    suppressed: SuppressedAnnotation,
+    /// 今の文書または章で注釈した語
+    seen: HashSet<String>,
+    /// 語ごとの、注釈した行番号
+    annotated_lines: HashMap<String, VecDeque<usize>>,
+    line: usize,
+    line_text: String,
+}
+
+impl<I> AnnotationIter<I> {
+    pub(crate) fn new(
+        inner: I,
+        policy: AnnotationPolicy,
+        suppressed: SuppressedAnnotation,
+    ) -> Self {
+        Self {
+            inner,
+            policy,
+            suppressed,
+            seen: HashSet::new(),
+            annotated_lines: HashMap::new(),
+            line: 0,
+            line_text: String::new(),
+        }
+    }
+
+    fn should_annotate(&mut self, key: String) -> bool {
+        match &self.policy {
+            AnnotationPolicy::Every => true,
+            AnnotationPolicy::FirstPerDocument | AnnotationPolicy::FirstPerSection { .. } => {
+                self.seen.insert(key)
+            }
+            AnnotationPolicy::MaxPerLines { max, lines } => {
+                let annotated = self.annotated_lines.entry(key).or_default();
+                while annotated
+                    .front()
+                    .is_some_and(|&line| line + lines <= self.line)
+                {
+                    annotated.pop_front();
+                }
+                if annotated.len() < *max {
+                    annotated.push_back(self.line);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    fn end_line(&mut self) {
+        if let AnnotationPolicy::FirstPerSection { separators } = &self.policy
+            && separators.iter().any(|s| s.trim() == self.line_text.trim())
+        {
+            self.seen.clear();
+        }
+        self.line += 1;
+        self.line_text.clear();
+    }
+}
+
+impl<I, S, DW> Iterator for AnnotationIter<I>
+where
+    I: Iterator<Item = ParsedFragment<S, DW>>,
+    S: Input<Item = char> + Copy,
+{
+    type Item = ParsedFragment<S, DW>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ParsedFragment { fragment, phrase } = self.inner.next()?;
+        if self.policy == AnnotationPolicy::Every {
+            return Some(ParsedFragment { fragment, phrase });
+        }
+        let phrase = match phrase {
+            Phrase::NewLine(_) => {
+                self.end_line();
+                phrase
+            }
+            Phrase::DictionaryWord(dw)
+                if !self.should_annotate(dw.target().iter_elements().collect()) =>
+            {
+                match self.suppressed {
+                    SuppressedAnnotation::Plain => {
+                        Phrase::new_plain(PlainPhrase::new(*dw.target()))
+                    }
+                    SuppressedAnnotation::Flag => {
+                        Phrase::new_dictionary_word(dw.with_suppressed(true))
+                    }
+                }
+            }
+            _ => phrase,
+        };
+        if matches!(self.policy, AnnotationPolicy::FirstPerSection { .. })
+            && !matches!(phrase, Phrase::NewLine(_))
+        {
+            self.line_text.extend(fragment.iter_elements());
+        }
+        Some(ParsedFragment { fragment, phrase })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DictionaryPhrase, DictionaryWord, GlossaryWord, ParseOptions, ParseText, Parser};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn annotated(parser: &Parser, text: &str, options: &ParseOptions) -> Vec<(usize, bool)> {
+        parser
+            .parse_iter_with_options(text, options)
+            .flat_map(|f| match f.phrase() {
+                Phrase::DictionaryWord(dw) => vec![(line_of(text, f.fragment()), !dw.suppressed())],
+                // 平文に変えた語は前後の平文とまとまる
+                Phrase::Plain(p) => p
+                    .target()
+                    .match_indices("好奇心")
+                    .map(|(i, _)| (line_of(text, &p.target()[i..]), false))
+                    .collect(),
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    /// 0始まりの行番号
+    fn line_of(text: &str, fragment: &str) -> usize {
+        text[..fragment.as_ptr() as usize - text.as_ptr() as usize]
+            .matches('\n')
+            .count()
+    }
+
+    const TEXT: &str = "好奇心と好奇心\n好奇心\n＊＊＊\n好奇心\n\n好奇心";
+
+    #[gtest]
+    #[rstest]
+    #[case(AnnotationPolicy::Every, vec![(0, true), (0, true), (1, true), (3, true), (5, true)])]
+    #[case(AnnotationPolicy::FirstPerDocument, vec![(0, true), (0, false), (1, false), (3, false), (5, false)])]
+    #[case(AnnotationPolicy::FirstPerSection { separators: vec!["＊＊＊".into()] }, vec![(0, true), (0, false), (1, false), (3, true), (5, false)])]
+    #[case(AnnotationPolicy::MaxPerLines { max: 1, lines: 3 }, vec![(0, true), (0, false), (1, false), (3, true), (5, false)])]
+    #[case(AnnotationPolicy::MaxPerLines { max: 2, lines: 2 }, vec![(0, true), (0, true), (1, false), (3, true), (5, true)])]
+    fn annotation_policy_works(
+        #[case] policy: AnnotationPolicy,
+        #[case] expected: Vec<(usize, bool)>,
+    ) -> anyhow::Result<()> {
+        let parser = Parser::try_new_with_dic(vec![DictionaryWord::new(
+            "好奇心".into(),
+            "こうきしん".into(),
+            "".into(),
+        )])?;
+        for suppressed in [SuppressedAnnotation::Plain, SuppressedAnnotation::Flag] {
+            let options = ParseOptions::default()
+                .with_annotation(policy.clone())
+                .with_suppressed_annotation(suppressed);
+            assert_that!(annotated(&parser, TEXT, &options), eq(&expected));
+        }
+        Ok(())
+    }
+
+    #[gtest]
+    fn suppressed_words_merge_into_surrounding_plain() -> anyhow::Result<()> {
+        let word = DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into());
+        let parser = Parser::try_new_with_dic(vec![word.clone()])?;
+        let options = ParseOptions::default().with_annotation(AnnotationPolicy::FirstPerDocument);
+        assert_that!(
+            parser
+                .parse_iter_with_options("茶屋と茶屋と茶屋\n茶屋", &options)
+                .map(|f| f.phrase().clone())
+                .collect::<Vec<_>>(),
+            eq(&vec![
+                Phrase::new_dictionary_word(DictionaryPhrase::new(
+                    "茶屋",
+                    GlossaryWord::Dictionary(&word)
+                )),
+                Phrase::new_plain(PlainPhrase::new("と茶屋と茶屋")),
+                Phrase::new_new_line(crate::NewLinePhrase::new(crate::NewLineType::Lf)),
+                Phrase::new_plain(PlainPhrase::new("茶屋")),
+            ])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn suppressed_annotation_works() -> anyhow::Result<()> {
+        let word = DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into());
+        let parser = Parser::try_new_with_dic(vec![word.clone()])?;
+        let options = ParseOptions::default().with_annotation(AnnotationPolicy::FirstPerDocument);
+        assert_that!(
+            parser
+                .parse_iter_with_options("茶屋茶屋", &options)
+                .map(|f| f.phrase().clone())
+                .collect::<Vec<_>>(),
+            eq(&vec![
+                Phrase::new_dictionary_word(DictionaryPhrase::new(
+                    "茶屋",
+                    GlossaryWord::Dictionary(&word)
+                )),
+                Phrase::new_plain(PlainPhrase::new("茶屋")),
+            ])
+        );
+        assert_that!(
+            parser
+                .parse_iter_with_options(
+                    "茶屋茶屋",
+                    &options.with_suppressed_annotation(SuppressedAnnotation::Flag)
+                )
+                .map(|f| f.phrase().clone())
+                .collect::<Vec<_>>(),
+            eq(&vec![
+                Phrase::new_dictionary_word(DictionaryPhrase::new(
+                    "茶屋",
+                    GlossaryWord::Dictionary(&word)
+                )),
+                Phrase::new_dictionary_word(
+                    DictionaryPhrase::new("茶屋", GlossaryWord::Dictionary(&word))
+                        .with_suppressed(true)
+                ),
+            ])
+        );
+        Ok(())
+    }
+}
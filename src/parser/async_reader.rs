@@ -0,0 +1,195 @@
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use futures::{Stream, io::AsyncBufRead};
+
+use crate::parser::{
+    ParsedFragment, context_parser::ContextParser, general_parser::GeneralParser,
+    general_parser::WordLookup, reader::parse_line,
+};
+
+/// [`AsyncBufRead`]から1行ずつ読んで解析する。チャンクの境目で文字やルビが切れても、
+/// 行がそろうまで解釈しないので、全体を一度に解析したときと同じ結果になる
+pub(crate) struct AsyncReaderParseStream<'a, R, D, CP>
+where
+    D: WordLookup<'a>,
+{
+    reader: R,
+    parser: &'a GeneralParser<D>,
+    context_parser: CP,
+    line: Vec<u8>,
+    pending: VecDeque<ParsedFragment<String, D::Word>>,
+    finished: bool,
+}
+
+impl<'a, R, D, CP> AsyncReaderParseStream<'a, R, D, CP>
+where
+    D: WordLookup<'a>,
+{
+    pub(crate) fn new(parser: &'a GeneralParser<D>, reader: R, context_parser: CP) -> Self {
+        Self {
+            reader,
+            parser,
+            context_parser,
+            line: vec![],
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+}
+
+// 読み手のほかにピン留めの必要なフィールドはない
+impl<'a, R, D, CP> Unpin for AsyncReaderParseStream<'a, R, D, CP>
+where
+    R: Unpin,
+    D: WordLookup<'a>,
+{
+}
+
+impl<'a, R, D, CP> Stream for AsyncReaderParseStream<'a, R, D, CP>
+where
+    R: AsyncBufRead + Unpin,
+    D: WordLookup<'a>,
+    CP: for<'s> ContextParser<&'s str, D::Word> + Clone,
+{
+    type Item = io::Result<ParsedFragment<String, D::Word>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(fragment) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(fragment)));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            let buf = match ready!(Pin::new(&mut this.reader).poll_fill_buf(cx)) {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    this.finished = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            };
+            let eof = buf.is_empty();
+            let (consumed, line_end) = match buf.iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (buf.len(), eof),
+            };
+            this.line.extend_from_slice(&buf[..consumed]);
+            Pin::new(&mut this.reader).consume(consumed);
+            if !line_end {
+                continue;
+            }
+            this.finished = eof;
+            let parsed = parse_line(
+                this.parser,
+                &this.line,
+                this.context_parser.clone(),
+                &mut this.pending,
+            );
+            this.line.clear();
+            if let Err(e) = parsed {
+                this.finished = true;
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{
+        StreamExt, TryStreamExt,
+        executor::block_on,
+        io::{AsyncRead, BufReader},
+    };
+
+    use crate::{
+        ParseText,
+        parser::test_fixture::{BROKEN_RUBY, KOKORO, SAMPLE, parser},
+    };
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    /// 1回に`chunk`バイトまでしか返さず、1回おきに`Pending`を返す読み手
+    struct Chunked<'a> {
+        bytes: &'a [u8],
+        chunk: usize,
+        ready: bool,
+    }
+
+    impl AsyncRead for Chunked<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = self.chunk.min(buf.len()).min(self.bytes.len());
+            buf[..len].copy_from_slice(&self.bytes[..len]);
+            self.bytes = &self.bytes[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    fn chunked(bytes: &[u8], chunk: usize) -> BufReader<Chunked<'_>> {
+        BufReader::new(Chunked {
+            bytes,
+            chunk,
+            ready: false,
+        })
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(SAMPLE)]
+    #[case(BROKEN_RUBY)]
+    #[case(KOKORO)]
+    fn parse_async_reader_matches_parse_iter(
+        #[case] text: &str,
+        #[values(1, 2, 3, 5, 8192)] chunk: usize,
+    ) -> anyhow::Result<()> {
+        let parser = parser();
+        let expected = parser
+            .parse_iter(text)
+            .map(|f| f.map_text(str::to_string))
+            .collect::<Vec<_>>();
+        let actual = block_on(
+            parser
+                .parse_async_reader(chunked(text.as_bytes(), chunk))
+                .try_collect::<Vec<_>>(),
+        )?;
+        assert_that!(actual, eq(&expected));
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_async_reader_rejects_invalid_utf8() {
+        let parser = parser();
+        let results = block_on(
+            parser
+                .parse_async_reader(chunked(b"\xe8\x8c\xb6\xe5\xb1\x8b\n\xe8\x8c", 2))
+                .map(|r| r.map_err(|e| e.kind()))
+                .collect::<Vec<_>>(),
+        );
+        assert_that!(
+            results,
+            elements_are![
+                ok(anything()),
+                ok(anything()),
+                err(eq(&io::ErrorKind::InvalidData))
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use nom::Input;
+
+use crate::{
+    DictionaryPhrase, GlossaryWord, Phrase, PlainPhrase, RubyPhrase, parser::ParsedFragment,
+};
+
+/// 覚える親文字の最小の文字数。1文字の親文字を覚えると、別の語の中に現れた同じ漢字にも読みを付けてしまう
+const MIN_LEARNED_CHARS: usize = 2;
+
+/// 作品中で振られたルビを覚え、ルビの無い平文から同じ語を見つけて[`GlossaryWord::Learned`]の語として返す。
+/// 辞書に一致した語は置き換えない
+pub(crate) struct AutoGlossaryIter<I, S, DW> {
+    inner: I,
+    enabled: bool,
+    /// 見出しとルビ。同じ見出しに別のルビが振られたら新しい方で上書きする
+    learned: HashMap<String, S>,
+    first_chars: HashSet<char>,
+    max_chars: usize,
+    key_buf: String,
+    pending: VecDeque<ParsedFragment<S, GlossaryWord<S, DW>>>,
+}
+
+impl<I, S, DW> AutoGlossaryIter<I, S, DW>
+where
+    I: Iterator<Item = ParsedFragment<S, DW>>,
+    S: Input<Item = char> + Copy,
+{
+    pub(crate) fn new(inner: I, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            learned: HashMap::new(),
+            first_chars: HashSet::new(),
+            max_chars: 0,
+            key_buf: String::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// 先読みした断片からルビを集めておく
+    pub(crate) fn with_pre_scan(
+        mut self,
+        fragments: impl Iterator<Item = ParsedFragment<S, DW>>,
+    ) -> Self {
+        for fragment in fragments {
+            if let Phrase::Ruby(ruby) = fragment.phrase()
+                && !self.learned.contains_key(&to_string(*ruby.target()))
+            {
+                self.learn(ruby);
+            }
+        }
+        self
+    }
+
+    fn learn(&mut self, ruby: &RubyPhrase<S>) {
+        let target = to_string(*ruby.target());
+        if target.chars().count() < MIN_LEARNED_CHARS || ruby.ruby().input_len() == 0 {
+            return;
+        }
+        if let Some(c) = target.chars().next() {
+            self.first_chars.insert(c);
+        }
+        self.max_chars = self.max_chars.max(target.chars().count());
+        self.learned.insert(target, *ruby.ruby());
+    }
+
+    /// 先頭に最長一致する覚えた語の長さ(`S`の単位)とルビ
+    fn longest_learned(&mut self, text: S) -> Option<(usize, S)> {
+        let first = text.iter_elements().next()?;
+        if !self.first_chars.contains(&first) {
+            return None;
+        }
+        self.key_buf.clear();
+        let mut best = None;
+        for (chars, c) in text.iter_elements().take(self.max_chars).enumerate() {
+            self.key_buf.push(c);
+            if let Some(ruby) = self.learned.get(&self.key_buf) {
+                best = Some((chars + 1, *ruby));
+            }
+        }
+        let (chars, ruby) = best?;
+        Some((text.slice_index(chars).ok()?, ruby))
+    }
+
+    /// 平文を覚えた語とそれ以外に分けて`pending`に積む
+    fn split_plain(&mut self, text: S) {
+        let mut plain_start = 0;
+        let mut pos = 0;
+        while pos < text.input_len() {
+            let here = text.take_from(pos);
+            if let Some((len, ruby)) = self.longest_learned(here) {
+                self.push_plain(text, plain_start, pos);
+                let target = here.take(len);
+                self.pending.push_back(ParsedFragment::new(
+                    target,
+                    Phrase::new_dictionary_word(DictionaryPhrase::new(
+                        target,
+                        GlossaryWord::Learned { reading: ruby },
+                    )),
+                ));
+                pos += len;
+                plain_start = pos;
+            } else {
+                pos += here.slice_index(1).unwrap_or(here.input_len()).max(1);
+            }
+        }
+        self.push_plain(text, plain_start, text.input_len());
+    }
+
+    fn push_plain(&mut self, text: S, start: usize, end: usize) {
+        if start < end {
+            let plain = text.take_from(start).take(end - start);
+            self.pending.push_back(ParsedFragment::new(
+                plain,
+                Phrase::new_plain(PlainPhrase::new(plain)),
+            ));
+        }
+    }
+}
+
+impl<I, S, DW> Iterator for AutoGlossaryIter<I, S, DW>
+where
+    I: Iterator<Item = ParsedFragment<S, DW>>,
+    S: Input<Item = char> + Copy,
+{
+    type Item = ParsedFragment<S, GlossaryWord<S, DW>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(fragment) = self.pending.pop_front() {
+            return Some(fragment);
+        }
+        let fragment = self.inner.next()?;
+        if self.enabled {
+            match fragment.phrase() {
+                Phrase::Ruby(ruby) => self.learn(ruby),
+                Phrase::Plain(plain) if !self.learned.is_empty() => {
+                    self.split_plain(*plain.target());
+                    return self.pending.pop_front();
+                }
+                _ => {}
+            }
+        }
+        let ParsedFragment { fragment, phrase } = fragment;
+        Some(ParsedFragment {
+            fragment,
+            phrase: phrase.map_word(GlossaryWord::Dictionary),
+        })
+    }
+}
+
+fn to_string<S>(s: S) -> String
+where
+    S: Input<Item = char>,
+{
+    s.iter_elements().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AutoGlossary, DictionaryWord, ParseOptions, ParseText, Parser, RubyType};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    type GlossaryPhrase<'a> = Phrase<&'a str, GlossaryWord<&'a str, &'a DictionaryWord>>;
+
+    fn learned(target: &'static str, ruby: &'static str) -> GlossaryPhrase<'static> {
+        Phrase::new_dictionary_word(DictionaryPhrase::new(
+            target,
+            GlossaryWord::Learned { reading: ruby },
+        ))
+    }
+
+    fn plain(target: &'static str) -> GlossaryPhrase<'static> {
+        Phrase::new_plain(PlainPhrase::new(target))
+    }
+
+    fn kanji_ruby(target: &'static str, ruby: &'static str) -> GlossaryPhrase<'static> {
+        Phrase::new_ruby(RubyPhrase::new(target, ruby, RubyType::KanjiWithRuby))
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(AutoGlossary::Off, vec![plain("玄人と"), kanji_ruby("玄人", "くろうと"), plain("の玄人芸")])]
+    #[case(AutoGlossary::Learn, vec![plain("玄人と"), kanji_ruby("玄人", "くろうと"), plain("の"), learned("玄人", "くろうと"), plain("芸")])]
+    #[case(AutoGlossary::PreScan, vec![learned("玄人", "くろうと"), plain("と"), kanji_ruby("玄人", "くろうと"), plain("の"), learned("玄人", "くろうと"), plain("芸")])]
+    fn auto_glossary_works(
+        #[case] mode: AutoGlossary,
+        #[case] expected: Vec<GlossaryPhrase<'static>>,
+    ) {
+        let parser = Parser::default();
+        let text = "玄人と玄人《くろうと》の玄人芸";
+        let fragments = parser
+            .parse_iter_with_options(text, &ParseOptions::default().with_auto_glossary(mode))
+            .collect::<Vec<_>>();
+        assert_that!(
+            fragments
+                .iter()
+                .map(|f| f.phrase().clone())
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        assert_that!(
+            fragments.iter().map(|f| *f.fragment()).collect::<String>(),
+            eq(text)
+        );
+    }
+
+    #[gtest]
+    fn auto_glossary_prefers_longest_and_latest() {
+        let parser = Parser::default();
+        let text = "|魔導《まどう》|魔導書《グリモア》|魔導《まとう》\n魔導書と魔導";
+        let phrases = parser
+            .parse_iter_with_options(
+                text,
+                &ParseOptions::default().with_auto_glossary(AutoGlossary::Learn),
+            )
+            .skip(4)
+            .map(|f| f.phrase().clone())
+            .collect::<Vec<_>>();
+        assert_that!(
+            phrases,
+            eq(&vec![
+                learned("魔導書", "グリモア"),
+                plain("と"),
+                learned("魔導", "まとう"),
+            ])
+        );
+    }
+
+    #[gtest]
+    fn auto_glossary_keeps_dictionary_words() -> anyhow::Result<()> {
+        let word = DictionaryWord::new("玄人芸".into(), "くろうとげい".into(), "".into());
+        let parser = Parser::try_new_with_dic(vec![word.clone()])?;
+        let phrases = parser
+            .parse_iter_with_options(
+                "玄人《くろうと》の玄人芸",
+                &ParseOptions::default().with_auto_glossary(AutoGlossary::Learn),
+            )
+            .map(|f| f.phrase().clone())
+            .collect::<Vec<_>>();
+        assert_that!(
+            phrases,
+            eq(&vec![
+                kanji_ruby("玄人", "くろうと"),
+                plain("の"),
+                Phrase::new_dictionary_word(DictionaryPhrase::new(
+                    "玄人芸",
+                    GlossaryWord::Dictionary(&word)
+                )),
+            ])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn auto_glossary_ignores_single_kanji_targets() {
+        let parser = Parser::default();
+        let phrases = parser
+            .parse_iter_with_options(
+                "眼《め》の眼鏡と眼",
+                &ParseOptions::default().with_auto_glossary(AutoGlossary::Learn),
+            )
+            .map(|f| f.phrase().clone())
+            .collect::<Vec<_>>();
+        assert_that!(
+            phrases,
+            eq(&vec![kanji_ruby("眼", "め"), plain("の眼鏡と眼")])
+        );
+    }
+}
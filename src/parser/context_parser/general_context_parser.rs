@@ -1,18 +1,35 @@
+use derive_new::new;
 use nom::{Compare, Input, Parser, branch::alt};
 
 use crate::parser::{
+    RubyLimits,
     context_parser::ContextParser,
-    nom_parsers::{kanji_ruby, ruby_instruction},
+    nom_parsers::{
+        char::{is_kanji, is_start_instruction},
+        kanji_ruby, ruby_instruction,
+    },
 };
 
-pub struct GeneralContextParser;
+#[derive(new, Copy, Clone, Default)]
+pub struct GeneralContextParser {
+    limits: RubyLimits,
+}
 
-impl<'a, S, WD> ContextParser<'a, S, WD> for GeneralContextParser
+impl<S, DW> ContextParser<S, DW> for GeneralContextParser
 where
     S: Input<Item = char> + Copy + Compare<&'static str>,
 {
     #[inline]
-    fn parse(input: S) -> nom::IResult<S, crate::parser::ParsedFragment<S, &'a WD>> {
-        alt((ruby_instruction, kanji_ruby)).parse(input)
+    fn parse(&self, input: S) -> nom::IResult<S, crate::parser::ParsedFragment<S, DW>> {
+        alt((
+            |i| ruby_instruction(i, &self.limits),
+            |i| kanji_ruby(i, &self.limits),
+        ))
+        .parse(input)
+    }
+
+    #[inline]
+    fn may_start_with(&self, c: char) -> bool {
+        is_start_instruction(c) || is_kanji(c)
     }
 }
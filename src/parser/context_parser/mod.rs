@@ -5,9 +5,17 @@ use crate::parser::ParsedFragment;
 
 pub(crate) use general_context_parser::*;
 
-pub trait ContextParser<'a, S, WD>
+pub trait ContextParser<S, DW>
 where
     S: Input<Item = char> + Copy + Compare<&'static str>,
 {
-    fn parse(input: S) -> IResult<S, ParsedFragment<S, &'a WD>>;
+    /// `Failure`を返すと、エラーの位置より前では`parse`を試さない。
+    /// 上限を超えて断った範囲の途中から解析し直さないために使う
+    fn parse(&self, input: S) -> IResult<S, ParsedFragment<S, DW>>;
+
+    /// `c`で始まる入力を解析できる見込みがあるか。`false`なら`parse`は必ず失敗する
+    #[inline]
+    fn may_start_with(&self, _c: char) -> bool {
+        true
+    }
 }
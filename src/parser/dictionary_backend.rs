@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use crawdad::Trie;
+use nom::Input;
+
+use crate::{
+    dictionary::validation::explain_build_error,
+    parser::{DictionaryWordContainer, Error, Result, trie_view::TrieView},
+};
+
+/// 見出しから語の番号を引く構造。語の番号は作るときに渡した語の並びでの位置
+pub trait DictionaryBackend: Sized {
+    /// 語の見出しから作る。語は1つ以上あり、見出しは空でなく重複しない
+    fn build<WD>(words: &[WD]) -> Result<Self>
+    where
+        WD: DictionaryWordContainer;
+
+    fn serialize(&self) -> Vec<u8>;
+
+    /// [`DictionaryBackend::serialize`]で書き出したバイト列から作り直す。
+    /// 壊れたバイト列でもpanicしないこと。語との突き合わせは[`DictionaryBackend::verify`]で行う
+    fn deserialize<WD>(bytes: &[u8], words: &[WD]) -> Result<Self>
+    where
+        WD: DictionaryWordContainer;
+
+    /// 書き出したバイト列が語と矛盾しないことを確かめる
+    fn verify<WD>(bytes: &[u8], words: &[WD]) -> Result<()>
+    where
+        WD: DictionaryWordContainer,
+    {
+        Self::deserialize(bytes, words).map(|_| ())
+    }
+
+    /// 見出しが完全に一致する語の番号
+    fn exact_match(&self, key: &str) -> Option<usize>;
+
+    /// 前方一致した語の番号と、一致した文字数を短い順に返す
+    fn common_prefix_search<S>(&self, text: S) -> impl Iterator<Item = (usize, usize)>
+    where
+        S: Input<Item = char>;
+
+    /// 最長一致した語の番号と、一致した文字数
+    #[inline]
+    fn longest_match<S>(&self, text: S) -> Option<(usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        self.common_prefix_search(text).last()
+    }
+}
+
+/// crawdadのダブル配列。既定の実装
+impl DictionaryBackend for Trie {
+    fn build<WD>(words: &[WD]) -> Result<Self>
+    where
+        WD: DictionaryWordContainer,
+    {
+        Trie::from_keys(words.iter().map(|w| w.word().key()))
+            .map_err(|e| explain_build_error(words, Error::new_create_dictionary(e)))
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        self.serialize_to_vec()
+    }
+
+    fn deserialize<WD>(bytes: &[u8], _words: &[WD]) -> Result<Self>
+    where
+        WD: DictionaryWordContainer,
+    {
+        // crawdadは読み込んだノードの添字を確かめずに引くので、範囲外を指すものを先に弾く
+        well_formed_trie(bytes)?;
+        Ok(Trie::deserialize_from_slice(bytes).0)
+    }
+
+    fn verify<WD>(bytes: &[u8], words: &[WD]) -> Result<()>
+    where
+        WD: DictionaryWordContainer,
+    {
+        let (trie, rest) = well_formed_trie(bytes)?;
+        if !rest.is_empty() {
+            return Err(Error::CorruptedDictionary(
+                "トライの後ろに余分なデータがあります",
+            ));
+        }
+        for (i, word) in words.iter().enumerate() {
+            if trie.exact_match(word.word().key().chars()) != Some(i as u32) {
+                return Err(Error::CorruptedDictionary("トライと語が一致しません"));
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn exact_match(&self, key: &str) -> Option<usize> {
+        Trie::exact_match(self, key.chars()).map(|i| i as usize)
+    }
+
+    #[inline]
+    fn common_prefix_search<S>(&self, text: S) -> impl Iterator<Item = (usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        Trie::common_prefix_search(self, text.iter_elements()).map(|(i, chars)| (i as usize, chars))
+    }
+}
+
+/// 長さが足りていて、どのノードも範囲内を指すトライ。
+/// 語の経路だけをたどっても、ほかの文字で範囲外を指すノードは見つからないので、すべてのノードを確かめる
+fn well_formed_trie(bytes: &[u8]) -> Result<(TrieView<'_>, &[u8])> {
+    let (trie, rest) =
+        TrieView::parse(bytes).ok_or(Error::CorruptedDictionary("トライが壊れています"))?;
+    if !trie.is_well_formed() {
+        return Err(Error::CorruptedDictionary("トライが範囲外を指しています"));
+    }
+    Ok((trie, rest))
+}
+
+/// 見出しを`HashMap`で引く。前方一致は最も長い見出しの文字数まで1文字ずつ引くので、小さな辞書向け。
+/// 語から作り直す方が速いので、何も書き出さない
+#[derive(Clone, Default, Debug)]
+pub struct HashMapBackend {
+    indices: HashMap<String, usize>,
+    max_chars: usize,
+}
+
+impl DictionaryBackend for HashMapBackend {
+    fn build<WD>(words: &[WD]) -> Result<Self>
+    where
+        WD: DictionaryWordContainer,
+    {
+        let mut backend = Self::default();
+        for (i, key) in words.iter().map(|w| w.word().key()).enumerate() {
+            if key.is_empty() || backend.indices.insert(key.clone(), i).is_some() {
+                return Err(explain_build_error(words, Error::EmptyDictionaryKey));
+            }
+            backend.max_chars = backend.max_chars.max(key.chars().count());
+        }
+        Ok(backend)
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    fn deserialize<WD>(bytes: &[u8], words: &[WD]) -> Result<Self>
+    where
+        WD: DictionaryWordContainer,
+    {
+        if !bytes.is_empty() {
+            return Err(Error::CorruptedDictionary(
+                "HashMapの辞書に余分なデータがあります",
+            ));
+        }
+        Self::build(words)
+    }
+
+    #[inline]
+    fn exact_match(&self, key: &str) -> Option<usize> {
+        self.indices.get(key).copied()
+    }
+
+    #[inline]
+    fn common_prefix_search<S>(&self, text: S) -> impl Iterator<Item = (usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        let mut key = String::new();
+        text.iter_elements()
+            .take(self.max_chars)
+            .enumerate()
+            .filter_map(move |(chars, c)| {
+                key.push(c);
+                self.indices.get(&key).map(|&i| (i, chars + 1))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DictionaryWord;
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn words() -> Vec<DictionaryWord> {
+        vec![
+            DictionaryWord::new("炎".into(), "ほのお".into(), "".into()),
+            DictionaryWord::new("炎炎".into(), "えんえん".into(), "".into()),
+            DictionaryWord::new("延々".into(), "えんえん".into(), "".into()),
+        ]
+    }
+
+    /// 書き出して作り直し、検証まで済ませたもの
+    fn round_trip<B: DictionaryBackend>() -> crate::Result<B> {
+        let words = words();
+        let backend = B::deserialize(&B::build(&words)?.serialize(), &words)?;
+        B::verify(&backend.serialize(), &words)?;
+        Ok(backend)
+    }
+
+    fn search<B: DictionaryBackend>(
+        backend: &B,
+        text: &str,
+    ) -> (Vec<(usize, usize)>, Option<usize>) {
+        (
+            DictionaryBackend::common_prefix_search(backend, text).collect(),
+            DictionaryBackend::exact_match(backend, text),
+        )
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("炎炎の炎", vec![(0, 1), (1, 2)], None)]
+    #[case("炎炎", vec![(0, 1), (1, 2)], Some(1))]
+    #[case("延々", vec![(2, 2)], Some(2))]
+    #[case("水", vec![], None)]
+    fn backends_agree(
+        #[case] text: &str,
+        #[case] matches: Vec<(usize, usize)>,
+        #[case] exact: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let expected = (matches, exact);
+        assert_that!(search(&round_trip::<Trie>()?, text), eq(&expected));
+        assert_that!(
+            search(&round_trip::<HashMapBackend>()?, text),
+            eq(&expected)
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn trie_rejects_nodes_pointing_out_of_bounds() -> anyhow::Result<()> {
+        let words = words();
+        let mut bytes = Trie::build(&words)?.serialize();
+        let table_len = u32::from_le_bytes(bytes[..4].try_into()?) as usize;
+        // 根のbase
+        bytes[4 + table_len * 4 + 8..][..4].copy_from_slice(&0x7000_0000u32.to_le_bytes());
+        assert_that!(
+            Trie::deserialize(&bytes, &words).err(),
+            some(matches_pattern!(Error::CorruptedDictionary(anything())))
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn hash_map_backend_reports_duplicate_key() {
+        let mut words = words();
+        words.push(DictionaryWord::new("炎".into(), "".into(), "".into()));
+        assert_that!(
+            HashMapBackend::build(&words).err(),
+            some(matches_pattern!(Error::InvalidDictionaryWords(len(eq(1)))))
+        );
+    }
+}
@@ -0,0 +1,76 @@
+/// 見出しの先頭に現れる文字の集合。基本多言語面の文字はビット表で、それ以外は並べて持つ
+#[derive(Clone)]
+pub(crate) struct FirstChars {
+    bmp: Box<[u64; 1024]>,
+    others: Vec<char>,
+}
+
+impl Default for FirstChars {
+    fn default() -> Self {
+        Self {
+            bmp: Box::new([0; 1024]),
+            others: vec![],
+        }
+    }
+}
+
+impl FromIterator<char> for FirstChars {
+    fn from_iter<T: IntoIterator<Item = char>>(iter: T) -> Self {
+        let mut chars = Self::default();
+        chars.extend(iter);
+        chars
+    }
+}
+
+impl Extend<char> for FirstChars {
+    fn extend<T: IntoIterator<Item = char>>(&mut self, iter: T) {
+        for c in iter {
+            self.insert(c);
+        }
+    }
+}
+
+impl FirstChars {
+    /// 見出しの先頭の文字を集める
+    pub(crate) fn from_keys<'k>(keys: impl IntoIterator<Item = &'k str>) -> Self {
+        keys.into_iter().filter_map(|k| k.chars().next()).collect()
+    }
+
+    pub(crate) fn insert(&mut self, c: char) {
+        let code = c as usize;
+        if code < 0x10000 {
+            self.bmp[code / 64] |= 1 << (code % 64);
+        } else if let Err(i) = self.others.binary_search(&c) {
+            self.others.insert(i, c);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn contains(&self, c: char) -> bool {
+        let code = c as usize;
+        if code < 0x10000 {
+            self.bmp[code / 64] & (1 << (code % 64)) != 0
+        } else {
+            self.others.binary_search(&c).is_ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    #[rstest]
+    #[case('炎', true)]
+    #[case('延', true)]
+    #[case('𠮷', true)]
+    #[case('々', false)]
+    #[case('𩸽', false)]
+    fn contains_works(#[case] c: char, #[case] expected: bool) {
+        let chars = FirstChars::from_keys(["炎炎", "延々", "𠮷野家", ""]);
+        assert_that!(chars.contains(c), eq(expected));
+    }
+}
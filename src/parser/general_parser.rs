@@ -1,106 +1,151 @@
-use std::marker::PhantomData;
-
-use nom::{Compare, Input, Parser, branch::alt};
+use nom::{Compare, Input, Parser, branch::alt, error::ErrorKind};
 
 use crate::{
-    DictionaryPhrase, Error, Phrase, PlainPhrase, PreparedDictionary,
+    DictionaryPhrase, GlossaryWord, Phrase, PlainPhrase,
     dictionary::DictionaryWord,
     parser::{
-        ParsedFragment, Result,
+        AutoGlossary, ParseOptions, ParsedFragment,
+        annotation::AnnotationIter,
+        auto_glossary::AutoGlossaryIter,
         context_parser::ContextParser,
-        nom_parsers::{new_line, space, tab, zenkaku_space},
-        parse_dictionary::DoubleArrayDictionary,
+        merge_plain::MergePlainIter,
+        nom_parsers::{
+            char::{is_new_line_escape, is_space, is_tab, is_zenkaku_space},
+            new_line, space, tab, zenkaku_space,
+        },
     },
 };
 
-pub(crate) struct GeneralParser<WD>
-where
-    WD: DictionaryWordContainer,
-{
-    dictionary: DoubleArrayDictionary<WD>,
+pub struct GeneralParser<D> {
+    dictionary: D,
 }
 
-impl<X> Default for GeneralParser<DictionaryWord<X>> {
-    fn default() -> Self {
-        Self {
-            dictionary: DoubleArrayDictionary::default(),
-        }
+impl<D> GeneralParser<D> {
+    pub(crate) fn new(dictionary: D) -> Self {
+        Self { dictionary }
     }
-}
 
-impl<WD> TryFrom<PreparedDictionary<WD>> for GeneralParser<WD>
-where
-    WD: Clone + DictionaryWordContainer,
-{
-    type Error = Error;
-    fn try_from(value: PreparedDictionary<WD>) -> std::result::Result<Self, Self::Error> {
-        Ok(Self {
-            dictionary: DoubleArrayDictionary::try_from(value)?,
-        })
+    pub(crate) fn dictionary(&self) -> &D {
+        &self.dictionary
     }
-}
 
-impl<X> GeneralParser<DictionaryWord<X>> {
-    pub(crate) fn try_new_bytes_with_dic(
-        words: impl Into<Vec<DictionaryWord<X>>>,
-    ) -> Result<GeneralParser<DictionaryWord<X>>> {
-        let words = words.into();
-        Ok(GeneralParser {
-            dictionary: DoubleArrayDictionary::<DictionaryWord<X>>::try_new(words)?,
-        })
+    pub(crate) fn dictionary_mut(&mut self) -> &mut D {
+        &mut self.dictionary
     }
 }
 
-impl<WD> GeneralParser<WD>
-where
-    WD: DictionaryWordContainer,
-{
+impl<D> GeneralParser<D> {
     pub fn parse_iter<'a, S, CP>(
         &'a self,
         text: S,
-    ) -> impl Iterator<Item = ParsedFragment<S, &'a WD>>
+        context_parser: CP,
+    ) -> impl Iterator<Item = ParsedFragment<S, D::Word>>
     where
         S: Input<Item = char> + Copy + Compare<&'static str>,
-        CP: ContextParser<'a, S, WD>,
+        D: WordLookup<'a>,
+        CP: ContextParser<S, D::Word>,
     {
         GeneralParseIter {
             text,
             dictionary: &self.dictionary,
+            context_parser,
+            rejected_until: None,
             plain_cache: None,
             next_phrase: None,
-            _cp: PhantomData::<CP>,
         }
     }
+
+    pub fn parse_iter_with_options<'a, S, CP>(
+        &'a self,
+        text: S,
+        options: &ParseOptions,
+        context_parser: CP,
+    ) -> impl Iterator<Item = ParsedFragment<S, GlossaryWord<S, D::Word>>>
+    where
+        S: Input<Item = char> + Copy + Compare<&'static str>,
+        D: WordLookup<'a>,
+        CP: ContextParser<S, D::Word> + Clone,
+    {
+        let iter = AutoGlossaryIter::new(
+            self.parse_iter(text, context_parser.clone()),
+            *options.auto_glossary() != AutoGlossary::Off,
+        );
+        let iter = if *options.auto_glossary() == AutoGlossary::PreScan {
+            iter.with_pre_scan(self.parse_iter(text, context_parser))
+        } else {
+            iter
+        };
+        let iter = AnnotationIter::new(
+            iter,
+            options.annotation().clone(),
+            *options.suppressed_annotation(),
+        );
+        MergePlainIter::new(iter, text)
+    }
 }
 
-pub struct GeneralParseIter<'a, CP, S, WD>
+pub struct GeneralParseIter<'a, CP, S, D>
 where
     S: Input<Item = char> + Copy + Compare<&'static str>,
-    WD: DictionaryWordContainer,
-    CP: ContextParser<'a, S, WD>,
+    D: WordLookup<'a>,
+    CP: ContextParser<S, D::Word>,
 {
     text: S,
-    dictionary: &'a DoubleArrayDictionary<WD>,
+    dictionary: &'a D,
+    context_parser: CP,
+    /// 文脈の解析が`Failure`で断った範囲の終わり(残りの長さ)。そこまでは文脈の解析を試さない
+    rejected_until: Option<usize>,
     plain_cache: Option<S>,
-    next_phrase: Option<ParsedFragment<S, &'a WD>>,
-    _cp: PhantomData<CP>,
+    next_phrase: Option<ParsedFragment<S, D::Word>>,
 }
 
-impl<'a, CP, S, WD> GeneralParseIter<'a, CP, S, WD>
+impl<'a, CP, S, D> GeneralParseIter<'a, CP, S, D>
 where
     S: Input<Item = char> + Copy + Compare<&'static str>,
-    WD: DictionaryWordContainer,
-    CP: ContextParser<'a, S, WD>,
+    D: WordLookup<'a>,
+    CP: ContextParser<S, D::Word>,
 {
+    /// `c`から何らかの語句が始まりうるか。`false`ならその位置は平文の続き
+    #[inline]
+    fn may_start_with(&self, c: char) -> bool {
+        self.context_parser.may_start_with(c)
+            || is_new_line_escape(c)
+            || is_space(c)
+            || is_zenkaku_space(c)
+            || is_tab(c)
+            || self.dictionary.may_start_with(c)
+    }
+
     #[inline]
-    fn parse_high_priority_once(&mut self) -> Option<(S, ParsedFragment<S, &'a WD>)> {
-        alt((CP::parse, new_line, space, zenkaku_space, tab))
-            .parse(self.text)
-            .ok()
+    fn parse_high_priority_once(&mut self) -> Option<(S, ParsedFragment<S, D::Word>)> {
+        let rejected = self
+            .rejected_until
+            .is_some_and(|end| self.text.input_len() > end);
+        let result = alt((
+            |i| {
+                if rejected {
+                    Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Fail)))
+                } else {
+                    self.context_parser.parse(i)
+                }
+            },
+            new_line,
+            space,
+            zenkaku_space,
+            tab,
+        ))
+        .parse(self.text);
+        match result {
+            Err(nom::Err::Failure(e)) => {
+                self.rejected_until = Some(e.input.input_len());
+                None
+            }
+            r => r.ok(),
+        }
     }
 
     #[inline]
-    fn parse_part_once(&mut self) -> Option<(S, ParsedFragment<S, &'a WD>)> {
+    fn parse_part_once(&mut self) -> Option<(S, ParsedFragment<S, D::Word>)> {
         if let Some(r) = self.parse_high_priority_once() {
             Some(r)
         } else {
@@ -108,7 +153,7 @@ where
         }
     }
     #[inline]
-    fn parse_once(&mut self) -> (Option<ParsedFragment<S, &'a WD>>, ParseStatus) {
+    fn parse_once(&mut self) -> (Option<ParsedFragment<S, D::Word>>, ParseStatus) {
         if let Some(next) = &self.next_phrase {
             let next = next.clone();
             self.next_phrase = None;
@@ -134,8 +179,13 @@ where
             if self.plain_cache.is_none() && self.text.input_len() > 0 {
                 self.plain_cache = Some(self.text);
             }
-            if let Some(next_char) = self.text.iter_elements().next() {
-                self.text = self.text.take_from(next_char.len_utf8());
+            if let Ok(first_len) = self.text.slice_index(1) {
+                // 何も始まりえない文字は、解析も辞書引きもせずに読み飛ばす
+                let rest = self.text.take_from(first_len);
+                let skip = rest
+                    .position(|c| self.may_start_with(c))
+                    .unwrap_or(rest.input_len());
+                self.text = rest.take_from(skip);
                 (None, ParseStatus::Progress)
             } else if let Some(plain) = self.plain_cache {
                 self.plain_cache = None;
@@ -153,14 +203,16 @@ where
     }
 
     #[inline]
-    fn parse_dictionary_phrase_once(&mut self) -> Option<(S, ParsedFragment<S, &'a WD>)> {
-        if let Some(word) = self.dictionary.get(self.text) {
-            let (text, fragment) = self.text.take_split(word.input_len());
+    fn parse_dictionary_phrase_once(&mut self) -> Option<(S, ParsedFragment<S, D::Word>)> {
+        if let Some((word, len, layer)) = self.dictionary.lookup_with_layer(self.text) {
+            let (text, fragment) = self.text.take_split(len);
             Some((
                 text,
                 ParsedFragment::new(
                     fragment,
-                    Phrase::new_dictionary_word(DictionaryPhrase::new(fragment, word)),
+                    Phrase::new_dictionary_word(
+                        DictionaryPhrase::new(fragment, word).with_layer(layer),
+                    ),
                 ),
             ))
         } else {
@@ -175,13 +227,13 @@ enum ParseStatus {
     End,
 }
 
-impl<'a, CP, S, WD> Iterator for GeneralParseIter<'a, CP, S, WD>
+impl<'a, CP, S, D> Iterator for GeneralParseIter<'a, CP, S, D>
 where
     S: Input<Item = char> + Copy + Compare<&'static str>,
-    WD: DictionaryWordContainer,
-    CP: ContextParser<'a, S, WD>,
+    D: WordLookup<'a>,
+    CP: ContextParser<S, D::Word>,
 {
-    type Item = ParsedFragment<S, &'a WD>;
+    type Item = ParsedFragment<S, D::Word>;
     fn next(&mut self) -> Option<Self::Item> {
         while let (phrase, status) = self.parse_once()
             && status == ParseStatus::Progress
@@ -194,6 +246,30 @@ where
     }
 }
 
+/// 辞書の実装を問わず、テキストの先頭に最長一致する語を引く
+pub trait WordLookup<'a> {
+    type Word: Clone;
+    /// 一致した語と、その長さ(`S`の単位)を返す
+    fn lookup<S>(&'a self, text: S) -> Option<(Self::Word, usize)>
+    where
+        S: Input<Item = char>;
+
+    /// `c`で始まる見出しがありうるか。`false`なら`c`から始まるテキストを引いても一致しない
+    #[inline]
+    fn may_start_with(&self, _c: char) -> bool {
+        true
+    }
+
+    /// [`WordLookup::lookup`]に加えて、語を見つけた層の番号を返す。層を持たない辞書では常に0
+    #[inline]
+    fn lookup_with_layer<S>(&'a self, text: S) -> Option<(Self::Word, usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        self.lookup(text).map(|(word, len)| (word, len, 0))
+    }
+}
+
 pub trait CharacterSize {}
 
 pub struct ByteCharacterSize;
@@ -216,3 +292,84 @@ impl<X> DictionaryWordContainer for DictionaryWord<X> {
         self.key().len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DictionaryWord, DictionaryWordKeyPhrase,
+        parser::{context_parser::GeneralContextParser, parse_dictionary::DoubleArrayDictionary},
+    };
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    /// 読み飛ばしをせず、すべての位置で解析と辞書引きをする
+    struct Exhaustive<D>(D);
+
+    impl<'a, D> WordLookup<'a> for Exhaustive<D>
+    where
+        D: WordLookup<'a>,
+    {
+        type Word = D::Word;
+
+        fn lookup<S>(&'a self, text: S) -> Option<(Self::Word, usize)>
+        where
+            S: Input<Item = char>,
+        {
+            self.0.lookup(text)
+        }
+    }
+
+    struct ExhaustiveContextParser;
+
+    impl<S, DW> ContextParser<S, DW> for ExhaustiveContextParser
+    where
+        S: Input<Item = char> + Copy + Compare<&'static str>,
+    {
+        fn parse(&self, input: S) -> nom::IResult<S, ParsedFragment<S, DW>> {
+            ContextParser::<S, DW>::parse(&GeneralContextParser::default(), input)
+        }
+    }
+
+    fn dictionary() -> DoubleArrayDictionary<DictionaryWord> {
+        DoubleArrayDictionary::try_new(vec![
+            DictionaryWord::new("どうして".into(), "".into(), "".into()),
+            DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into()),
+            DictionaryWord::new_all(
+                vec![
+                    DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                    DictionaryWordKeyPhrase::new_plain("しい".into()),
+                ],
+                "".into(),
+                (),
+            ),
+            DictionaryWord::new("ABC".into(), "".into(), "".into()),
+            DictionaryWord::new("𠮷野".into(), "よしの".into(), "".into()),
+        ])
+        .unwrap()
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("")]
+    #[case("あいうえお")]
+    #[case("それはどうしてか、若々しい茶屋の娘は\r\n言った。")]
+    #[case("ABCとAB C　と\tABCD")]
+    #[case("|玄人《くろうと》と玄人（くろうと）と｜玄人(くろうと")]
+    #[case("𠮷野家の茶屋《ちゃや》で\n\r\nどうし")]
+    #[case("お茶屋《ちゃや》と若々茶屋《ちゃや》")]
+    #[case(&("素".repeat(70) + "茶屋《ちゃや》と素茶屋《ちゃや》"))]
+    #[case(include_str!("../../benches/test_data/kokoro_utf8.txt"))]
+    fn skipping_plain_text_keeps_output(#[case] text: &str) {
+        let fast = GeneralParser::new(dictionary());
+        let exhaustive = GeneralParser::new(Exhaustive(dictionary()));
+        assert_that!(
+            fast.parse_iter(text, GeneralContextParser::default())
+                .collect::<Vec<_>>(),
+            eq(&exhaustive
+                .parse_iter(text, ExhaustiveContextParser)
+                .collect::<Vec<_>>())
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::ops::Range;
+
+use derive_getters::Getters;
+use derive_new::new;
+
+use crate::parser::{
+    ParsedFragment, context_parser::ContextParser, general_parser::GeneralParser,
+    general_parser::WordLookup,
+};
+
+type LineParser<'a, W> = Box<dyn Fn(&str) -> Vec<ParsedFragment<String, W>> + 'a>;
+
+/// 編集で置き換わった語句の範囲
+#[derive(new, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct FragmentSplice {
+    /// 編集前の語句の並びで取り除いた範囲
+    removed: Range<usize>,
+    /// 編集後の語句の並びで新しく入った範囲
+    inserted: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LineSpan {
+    /// 末尾の改行を含むバイト数
+    bytes: usize,
+    fragments: usize,
+}
+
+/// 解析した結果を持っておき、編集された行だけを解析し直す。ルビは改行をまたがないので、
+/// 改行を含む見出しの語がなければ、結果はいつも全体を解析し直したものと同じ
+pub struct IncrementalParse<'a, W> {
+    parse_line: LineParser<'a, W>,
+    text: String,
+    /// 改行ごとに区切った行。最後の行だけは改行で終わらず、空のこともある
+    lines: Vec<LineSpan>,
+    fragments: Vec<ParsedFragment<String, W>>,
+}
+
+impl<'a, W> IncrementalParse<'a, W> {
+    pub(crate) fn new<D, CP>(parser: &'a GeneralParser<D>, context_parser: CP, text: String) -> Self
+    where
+        D: WordLookup<'a, Word = W>,
+        CP: for<'s> ContextParser<&'s str, W> + Clone + 'a,
+    {
+        let mut re = Self {
+            parse_line: Box::new(move |line| {
+                parser
+                    .parse_iter(line, context_parser.clone())
+                    .map(|f| f.map_text(str::to_string))
+                    .collect()
+            }),
+            text: String::new(),
+            lines: vec![LineSpan {
+                bytes: 0,
+                fragments: 0,
+            }],
+            fragments: vec![],
+        };
+        re.edit(0..0, &text);
+        re
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn fragments(&self) -> &[ParsedFragment<String, W>] {
+        &self.fragments
+    }
+
+    /// `range`のバイトを`new_text`に置き換え、編集の及ぶ行だけを解析し直す
+    ///
+    /// # Panics
+    ///
+    /// [`String::replace_range`]と同じく、`range`が文字の境目にないか、テキストの外にあるとき
+    pub fn edit(&mut self, range: Range<usize>, new_text: &str) -> FragmentSplice {
+        self.text.replace_range(range.clone(), new_text);
+        let (first, region_start) = self.line_at(range.start);
+        let (last, last_start) = self.line_at(range.end);
+        let region_end = last_start + self.lines[last].bytes - range.len() + new_text.len();
+        let is_last_line = last == self.lines.len() - 1;
+
+        let region = &self.text[region_start..region_end];
+        let mut lines = region.split_inclusive('\n').collect::<Vec<_>>();
+        if is_last_line && (region.is_empty() || region.ends_with('\n')) {
+            lines.push("");
+        }
+        let mut spans = Vec::with_capacity(lines.len());
+        let mut fragments = vec![];
+        for line in lines {
+            let parsed = (self.parse_line)(line);
+            spans.push(LineSpan {
+                bytes: line.len(),
+                fragments: parsed.len(),
+            });
+            fragments.extend(parsed);
+        }
+
+        let start = self.lines[..first]
+            .iter()
+            .map(|l| l.fragments)
+            .sum::<usize>();
+        let removed = self.lines[first..=last]
+            .iter()
+            .map(|l| l.fragments)
+            .sum::<usize>();
+        let inserted = fragments.len();
+        self.lines.splice(first..=last, spans);
+        self.fragments.splice(start..start + removed, fragments);
+        FragmentSplice::new(start..start + removed, start..start + inserted)
+    }
+
+    /// 編集前のバイト位置`pos`を含む行の番号と、その行の始まり
+    fn line_at(&self, pos: usize) -> (usize, usize) {
+        let mut start = 0;
+        for (i, line) in self.lines.iter().enumerate() {
+            if pos < start + line.bytes || i == self.lines.len() - 1 {
+                return (i, start);
+            }
+            start += line.bytes;
+        }
+        unreachable!("最後の行が必ずある")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DictionaryWord, ParseText, Parser, parser::test_fixture::parser};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn full_parse<'a>(
+        parser: &'a Parser,
+        text: &str,
+    ) -> Vec<ParsedFragment<String, &'a DictionaryWord>> {
+        parser
+            .parse_iter(text)
+            .map(|f| f.map_text(str::to_string))
+            .collect()
+    }
+
+    const TEXT: &str = "若々しい茶屋 の娘\r\n|玄人《くろうと》と\n\n茶屋";
+
+    #[gtest]
+    #[rstest]
+    #[case(0..0, "")]
+    #[case(0..0, "茶屋")]
+    #[case(12..12, "茶")]
+    #[case(12..15, "")]
+    #[case(18..19, "")]
+    #[case(25..26, "")]
+    #[case(26..27, "")]
+    #[case(26..26, "\n")]
+    #[case(25..25, " 茶屋\n 茶屋")]
+    #[case(49..52, "")]
+    #[case(55..56, "")]
+    #[case(56..56, "\n\n")]
+    #[case(27..57, "")]
+    #[case(63..63, "\n")]
+    #[case(63..63, "茶屋")]
+    #[case(0..63, "")]
+    #[case(0..63, "|漢字《かんじ》\n")]
+    fn edit_matches_full_parse(#[case] range: Range<usize>, #[case] new_text: &str) {
+        let parser = parser();
+        let mut incremental = parser.parse_incremental(TEXT);
+        let before = incremental.fragments().to_vec();
+        let splice = incremental.edit(range.clone(), new_text);
+
+        let mut text = TEXT.to_string();
+        text.replace_range(range, new_text);
+        let expected = full_parse(&parser, &text);
+        assert_that!(incremental.text(), eq(text.as_str()));
+        assert_that!(incremental.fragments(), eq(expected.as_slice()));
+
+        // 置き換わった範囲の外は編集前と同じ
+        let mut spliced = before;
+        spliced.splice(
+            splice.removed().clone(),
+            expected[splice.inserted().clone()].iter().cloned(),
+        );
+        assert_that!(spliced, eq(&expected));
+    }
+
+    #[gtest]
+    fn edit_reparses_only_affected_lines() {
+        let parser = parser();
+        let mut incremental = parser.parse_incremental(TEXT);
+        // 2行目の閉じ括弧を消すと、ルビでなくなる
+        let splice = incremental.edit(49..52, "");
+        assert_that!(splice, eq(&FragmentSplice::new(4..7, 4..6)));
+        // 3行目の空行への入力は、その行だけ
+        let splice = incremental.edit(53..53, "茶屋");
+        assert_that!(splice, eq(&FragmentSplice::new(6..7, 6..8)));
+    }
+
+    #[gtest]
+    fn typing_one_char_at_a_time() {
+        let parser = parser();
+        let mut incremental = parser.parse_incremental("");
+        for (pos, c) in TEXT.char_indices() {
+            incremental.edit(pos..pos, c.encode_utf8(&mut [0; 4]));
+            assert_that!(
+                incremental.fragments(),
+                eq(full_parse(&parser, &TEXT[..pos + c.len_utf8()]).as_slice())
+            );
+        }
+    }
+}
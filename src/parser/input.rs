@@ -0,0 +1,391 @@
+use std::{
+    fmt::{self, Display},
+    iter::{Copied, Enumerate, Map},
+    slice,
+};
+
+use nom::{Compare, CompareResult, Input, Needed, Offset};
+
+/// UTF-16のコード単位の列を解析の入力にする。語句の長さや位置はコード単位で数える。
+/// 対になっていないサロゲートは1単位の`U+FFFD`として読む
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Utf16Input<'a>(&'a [u16]);
+
+impl<'a> Utf16Input<'a> {
+    pub fn new(units: &'a [u16]) -> Self {
+        Self(units)
+    }
+
+    pub fn as_slice(&self) -> &'a [u16] {
+        self.0
+    }
+
+    /// `whole`の先頭からの位置(コード単位)。`self`は`whole`の一部であること
+    pub fn offset_in(&self, whole: Self) -> usize {
+        whole.offset(self)
+    }
+}
+
+impl<'a> From<&'a [u16]> for Utf16Input<'a> {
+    fn from(value: &'a [u16]) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for Utf16Input<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.iter_elements().try_for_each(|c| write!(f, "{c}"))
+    }
+}
+
+/// [`Utf16Input`]の文字と、その位置(コード単位)
+#[derive(Clone, Debug)]
+pub struct Utf16CharIndices<'a> {
+    units: &'a [u16],
+    pos: usize,
+}
+
+impl Iterator for Utf16CharIndices<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = *self.units.get(self.pos)?;
+        let c = char::decode_utf16([first, self.units.get(self.pos + 1).copied().unwrap_or(0)])
+            .next()
+            .and_then(|c| c.ok())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        let pos = self.pos;
+        self.pos += c.len_utf16();
+        Some((pos, c))
+    }
+}
+
+impl<'a> Input for Utf16Input<'a> {
+    type Item = char;
+    type Iter = Map<Utf16CharIndices<'a>, fn((usize, char)) -> char>;
+    type IterIndices = Utf16CharIndices<'a>;
+
+    #[inline]
+    fn input_len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn take(&self, index: usize) -> Self {
+        Self(&self.0[..index])
+    }
+
+    #[inline]
+    fn take_from(&self, index: usize) -> Self {
+        Self(&self.0[index..])
+    }
+
+    #[inline]
+    fn take_split(&self, index: usize) -> (Self, Self) {
+        let (prefix, suffix) = self.0.split_at(index);
+        (Self(suffix), Self(prefix))
+    }
+
+    #[inline]
+    fn position<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        self.iter_indices()
+            .find(|&(_, c)| predicate(c))
+            .map(|(i, _)| i)
+    }
+
+    #[inline]
+    fn iter_elements(&self) -> Self::Iter {
+        self.iter_indices().map(|(_, c)| c)
+    }
+
+    #[inline]
+    fn iter_indices(&self) -> Self::IterIndices {
+        Utf16CharIndices {
+            units: self.0,
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    fn slice_index(&self, count: usize) -> Result<usize, Needed> {
+        slice_index(self.iter_indices(), self.input_len(), count)
+    }
+}
+
+impl Compare<&str> for Utf16Input<'_> {
+    fn compare(&self, t: &str) -> CompareResult {
+        compare_chars(self.iter_elements(), t, |a, b| a == b)
+    }
+
+    fn compare_no_case(&self, t: &str) -> CompareResult {
+        compare_chars(self.iter_elements(), t, |a, b| {
+            a.to_lowercase().eq(b.to_lowercase())
+        })
+    }
+}
+
+impl Offset for Utf16Input<'_> {
+    fn offset(&self, second: &Self) -> usize {
+        (second.0.as_ptr() as usize - self.0.as_ptr() as usize) / size_of::<u16>()
+    }
+}
+
+/// `char`の列を解析の入力にする。語句の長さや位置は文字数で数える
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CharsInput<'a>(&'a [char]);
+
+impl<'a> CharsInput<'a> {
+    pub fn new(chars: &'a [char]) -> Self {
+        Self(chars)
+    }
+
+    pub fn as_slice(&self) -> &'a [char] {
+        self.0
+    }
+
+    /// `whole`の先頭からの位置(文字数)。`self`は`whole`の一部であること
+    pub fn offset_in(&self, whole: Self) -> usize {
+        whole.offset(self)
+    }
+}
+
+impl<'a> From<&'a [char]> for CharsInput<'a> {
+    fn from(value: &'a [char]) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for CharsInput<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|c| write!(f, "{c}"))
+    }
+}
+
+impl<'a> Input for CharsInput<'a> {
+    type Item = char;
+    type Iter = Copied<slice::Iter<'a, char>>;
+    type IterIndices = Enumerate<Self::Iter>;
+
+    #[inline]
+    fn input_len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn take(&self, index: usize) -> Self {
+        Self(&self.0[..index])
+    }
+
+    #[inline]
+    fn take_from(&self, index: usize) -> Self {
+        Self(&self.0[index..])
+    }
+
+    #[inline]
+    fn take_split(&self, index: usize) -> (Self, Self) {
+        let (prefix, suffix) = self.0.split_at(index);
+        (Self(suffix), Self(prefix))
+    }
+
+    #[inline]
+    fn position<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        self.0.iter().position(|&c| predicate(c))
+    }
+
+    #[inline]
+    fn iter_elements(&self) -> Self::Iter {
+        self.0.iter().copied()
+    }
+
+    #[inline]
+    fn iter_indices(&self) -> Self::IterIndices {
+        self.iter_elements().enumerate()
+    }
+
+    #[inline]
+    fn slice_index(&self, count: usize) -> Result<usize, Needed> {
+        if count <= self.0.len() {
+            Ok(count)
+        } else {
+            Err(Needed::Unknown)
+        }
+    }
+}
+
+impl Compare<&str> for CharsInput<'_> {
+    fn compare(&self, t: &str) -> CompareResult {
+        compare_chars(self.iter_elements(), t, |a, b| a == b)
+    }
+
+    fn compare_no_case(&self, t: &str) -> CompareResult {
+        compare_chars(self.iter_elements(), t, |a, b| {
+            a.to_lowercase().eq(b.to_lowercase())
+        })
+    }
+}
+
+impl Offset for CharsInput<'_> {
+    fn offset(&self, second: &Self) -> usize {
+        (second.0.as_ptr() as usize - self.0.as_ptr() as usize) / size_of::<char>()
+    }
+}
+
+/// 先頭から`count`文字の長さ。`&str`の実装と同じく、ちょうど末尾までなら全体の長さ
+pub(crate) fn slice_index(
+    indices: impl Iterator<Item = (usize, char)>,
+    len: usize,
+    count: usize,
+) -> Result<usize, Needed> {
+    let mut chars = 0;
+    for (index, _) in indices {
+        if chars == count {
+            return Ok(index);
+        }
+        chars += 1;
+    }
+    if chars == count {
+        Ok(len)
+    } else {
+        Err(Needed::Unknown)
+    }
+}
+
+pub(crate) fn compare_chars(
+    mut input: impl Iterator<Item = char>,
+    tag: &str,
+    eq: impl Fn(char, char) -> bool,
+) -> CompareResult {
+    for t in tag.chars() {
+        match input.next() {
+            Some(c) if eq(c, t) => {}
+            Some(_) => return CompareResult::Error,
+            None => return CompareResult::Incomplete,
+        }
+    }
+    CompareResult::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DictionaryWord, ParseText, ParsedFragment, Parser, Phrase};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn parser() -> Parser {
+        Parser::try_new_with_dic(vec![
+            DictionaryWord::new("若々しい".into(), "わかわか".into(), "".into()),
+            DictionaryWord::new("𠮷野".into(), "よしの".into(), "".into()),
+            DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into()),
+        ])
+        .unwrap()
+    }
+
+    fn kind<S, DW>(phrase: &Phrase<S, DW>) -> &'static str {
+        match phrase {
+            Phrase::Plain(_) => "plain",
+            Phrase::Ruby(_) => "ruby",
+            Phrase::DictionaryWord(_) => "word",
+            Phrase::NewLine(_) => "new_line",
+            Phrase::WhiteSpace(_) => "white_space",
+        }
+    }
+
+    /// 語句ごとの位置と長さ(入力の単位)、本文、種類
+    fn summary<S, DW>(
+        fragments: impl Iterator<Item = ParsedFragment<S, DW>>,
+        offset: impl Fn(&S) -> usize,
+    ) -> Vec<(usize, usize, String, &'static str)>
+    where
+        S: Input<Item = char> + Display,
+    {
+        fragments
+            .map(|f| {
+                (
+                    offset(f.fragment()),
+                    f.fragment().input_len(),
+                    f.fragment().to_string(),
+                    kind(f.phrase()),
+                )
+            })
+            .collect()
+    }
+
+    #[gtest]
+    fn utf16_offsets_are_code_units() {
+        let parser = parser();
+        // U+FFFDの位置に対になっていないサロゲートを置く
+        let units = "𠮷野の茶屋\r\n|若々しい《わかわか》娘　と\u{FFFD}ABC"
+            .encode_utf16()
+            .map(|u| if u == 0xFFFD { 0xD800 } else { u })
+            .collect::<Vec<_>>();
+        let input = Utf16Input::new(&units);
+        assert_that!(
+            summary(parser.parse_iter(input), |f| f.offset_in(input)),
+            elements_are![
+                eq(&(0, 3, "𠮷野".to_string(), "word")),
+                eq(&(3, 1, "の".to_string(), "plain")),
+                eq(&(4, 2, "茶屋".to_string(), "word")),
+                eq(&(6, 2, "\r\n".to_string(), "new_line")),
+                eq(&(8, 11, "|若々しい《わかわか》".to_string(), "ruby")),
+                eq(&(19, 1, "娘".to_string(), "plain")),
+                eq(&(20, 1, "　".to_string(), "white_space")),
+                eq(&(21, 5, "と\u{FFFD}ABC".to_string(), "plain")),
+            ]
+        );
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("若々しい茶屋の娘\r\n|玄人《くろうと》と\n\n𠮷野")]
+    #[case(include_str!("../../benches/test_data/kokoro_utf8.txt"))]
+    fn inputs_agree_with_str(#[case] text: &str) {
+        let parser = parser();
+        let fragments = summary(parser.parse_iter(text), |_| 0);
+        // 語句は隙間なく並ぶので、手前の語句の長さを足せば位置になる
+        let in_unit = |count: fn(&str) -> usize| {
+            fragments
+                .iter()
+                .scan(0, |offset, (_, _, s, kind)| {
+                    let len = count(s);
+                    *offset += len;
+                    Some((*offset - len, len, s.clone(), *kind))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let chars = text.chars().collect::<Vec<_>>();
+        let input = CharsInput::new(&chars);
+        assert_that!(
+            summary(parser.parse_iter(input), |f| f.offset_in(input)),
+            eq(&in_unit(|s| s.chars().count()))
+        );
+
+        let units = text.encode_utf16().collect::<Vec<_>>();
+        let input = Utf16Input::new(&units);
+        assert_that!(
+            summary(parser.parse_iter(input), |f| f.offset_in(input)),
+            eq(&in_unit(|s| s.encode_utf16().count()))
+        );
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("\r\n", CompareResult::Ok)]
+    #[case("\r", CompareResult::Ok)]
+    #[case("\r\nあい", CompareResult::Incomplete)]
+    #[case("\n", CompareResult::Error)]
+    fn compare(#[case] tag: &str, #[case] expected: CompareResult) {
+        let units = "\r\nあ".encode_utf16().collect::<Vec<_>>();
+        let chars = "\r\nあ".chars().collect::<Vec<_>>();
+        assert_that!(Utf16Input::new(&units).compare(tag), eq(&expected));
+        assert_that!(CharsInput::new(&chars).compare(tag), eq(&expected));
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use nom::Input;
+
+use crate::parser::{
+    DictionaryBackend, DictionaryWordContainer, WordLookup, parse_dictionary::DoubleArrayDictionary,
+};
+
+/// 複数の辞書を積み重ねて引く。一致の長い語を優先し、長さが同じなら後から積んだ層を優先する
+pub struct LayeredDictionary<D> {
+    layers: Vec<D>,
+}
+
+impl<D> Default for LayeredDictionary<D> {
+    fn default() -> Self {
+        Self { layers: vec![] }
+    }
+}
+
+impl<D> From<D> for LayeredDictionary<D> {
+    fn from(value: D) -> Self {
+        Self {
+            layers: vec![value],
+        }
+    }
+}
+
+impl<D> FromIterator<D> for LayeredDictionary<D> {
+    fn from_iter<T: IntoIterator<Item = D>>(iter: T) -> Self {
+        Self {
+            layers: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<D> LayeredDictionary<D> {
+    /// 最も優先する層として積み、その層の番号を返す
+    pub(crate) fn push(&mut self, layer: D) -> usize {
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
+
+    pub(crate) fn layers(&self) -> &[D] {
+        &self.layers
+    }
+}
+
+impl<WD, B> LayeredDictionary<DoubleArrayDictionary<WD, B>>
+where
+    WD: DictionaryWordContainer,
+    B: DictionaryBackend,
+{
+    /// 見出しが完全に一致する語。複数の層にあれば優先する層のもの
+    pub(crate) fn get(&self, key: &str) -> Option<&WD> {
+        self.layers.iter().rev().find_map(|layer| layer.get(key))
+    }
+
+    /// 見出しが`text`の先頭に一致する語
+    pub(crate) fn common_prefix_search(&self, text: &str) -> Vec<&WD> {
+        self.merge(|layer| {
+            layer
+                .common_prefix_matches(text)
+                .map(|(i, _)| &layer.words()[i])
+                .collect()
+        })
+    }
+
+    /// 見出しが`prefix`で始まる語
+    pub(crate) fn predictive_search(&self, prefix: &str) -> Vec<&WD> {
+        self.merge(|layer| layer.predictive_search(prefix).collect())
+    }
+
+    /// 読みが一致する語
+    pub(crate) fn search_by_reading(&self, reading: &str) -> Vec<&WD> {
+        self.merge(|layer| layer.search_by_reading(reading).collect())
+    }
+
+    /// 読みが`prefix`で始まる語
+    pub(crate) fn predictive_search_by_reading(&self, prefix: &str) -> Vec<&WD> {
+        self.merge(|layer| layer.predictive_search_by_reading(prefix).collect())
+    }
+
+    /// 各層の結果をまとめる。同じ見出しは優先する層のものだけを残す
+    fn merge<'a>(
+        &'a self,
+        search: impl Fn(&'a DoubleArrayDictionary<WD, B>) -> Vec<&'a WD>,
+    ) -> Vec<&'a WD> {
+        let mut keys = HashSet::new();
+        self.layers
+            .iter()
+            .rev()
+            .flat_map(search)
+            .filter(|w| keys.insert(w.word().key().as_str()))
+            .collect()
+    }
+}
+
+impl<'a, D> WordLookup<'a> for LayeredDictionary<D>
+where
+    D: WordLookup<'a>,
+{
+    type Word = D::Word;
+
+    #[inline]
+    fn lookup<S>(&'a self, text: S) -> Option<(Self::Word, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        self.lookup_with_layer(text)
+            .map(|(word, len, _)| (word, len))
+    }
+
+    #[inline]
+    fn may_start_with(&self, c: char) -> bool {
+        self.layers.iter().any(|layer| layer.may_start_with(c))
+    }
+
+    #[inline]
+    fn lookup_with_layer<S>(&'a self, text: S) -> Option<(Self::Word, usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        // 優先する層から引き、より長く一致したときだけ置き換える
+        self.layers
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(layer, dictionary)| {
+                dictionary
+                    .lookup(text.clone())
+                    .map(|(word, len)| (word, len, layer))
+            })
+            .fold(None, |best, hit| match best {
+                Some((_, best_len, _)) if hit.1 <= best_len => best,
+                _ => Some(hit),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DictionaryWord, parser::parse_dictionary::DoubleArrayDictionary};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn layers() -> LayeredDictionary<DoubleArrayDictionary<DictionaryWord>> {
+        [
+            vec![
+                DictionaryWord::new("魔導".into(), "まどう".into(), "共通".into()),
+                DictionaryWord::new("魔導書".into(), "まどうしょ".into(), "共通".into()),
+            ],
+            vec![
+                DictionaryWord::new("魔導".into(), "マドウ".into(), "作品".into()),
+                DictionaryWord::new("魔".into(), "ま".into(), "作品".into()),
+            ],
+        ]
+        .into_iter()
+        .map(|words| DoubleArrayDictionary::try_new(words).unwrap())
+        .collect()
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("魔導書を読む", Some(("魔導書", "共通", 0)))]
+    #[case("魔導を学ぶ", Some(("魔導", "作品", 1)))]
+    #[case("魔法", Some(("魔", "作品", 1)))]
+    #[case("剣", None)]
+    fn lookup_with_layer_works(#[case] text: &str, #[case] expected: Option<(&str, &str, usize)>) {
+        let dic = layers();
+        assert_that!(
+            dic.lookup_with_layer(text).map(|(w, len, layer)| (
+                w.key().as_str(),
+                w.description().as_str(),
+                len,
+                layer
+            )),
+            eq(expected.map(|(key, description, layer)| (key, description, key.len(), layer)))
+        );
+    }
+
+    #[gtest]
+    fn push_works() {
+        let mut dic = LayeredDictionary::<DoubleArrayDictionary<DictionaryWord>>::default();
+        assert_that!(dic.lookup("魔導"), none());
+        let layer = dic.push(
+            DoubleArrayDictionary::try_new(vec![DictionaryWord::new(
+                "魔導".into(),
+                "".into(),
+                "".into(),
+            )])
+            .unwrap(),
+        );
+        assert_that!(layer, eq(0));
+        assert_that!(dic.layers().len(), eq(1));
+        assert_that!(
+            dic.lookup("魔導").map(|(_, len)| len),
+            some(eq("魔導".len()))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use nom::Input;
+
+use crate::{Phrase, PlainPhrase, parser::ParsedFragment};
+
+/// 続けて現れた平文を1つにまとめる。注釈しない語を平文に変えると平文が続くことがあるが、
+/// [`crate::ParseText::parse_iter`]はそうした並びを返さないので、それに揃える。
+/// `inner`の語句は`text`を先頭から隙間なく区切ったものであること
+pub(crate) struct MergePlainIter<I, S, DW> {
+    inner: I,
+    text: S,
+    /// `inner`から受け取った語句の終わりの、`text`での位置
+    pos: usize,
+    pending: Option<ParsedFragment<S, DW>>,
+}
+
+impl<I, S, DW> MergePlainIter<I, S, DW> {
+    pub(crate) fn new(inner: I, text: S) -> Self {
+        Self {
+            inner,
+            text,
+            pos: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<I, S, DW> Iterator for MergePlainIter<I, S, DW>
+where
+    I: Iterator<Item = ParsedFragment<S, DW>>,
+    S: Input<Item = char> + Copy,
+{
+    type Item = ParsedFragment<S, DW>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(fragment) = self.pending.take() {
+            return Some(fragment);
+        }
+        let start = self.pos;
+        for fragment in self.inner.by_ref() {
+            self.pos += fragment.fragment.input_len();
+            if matches!(fragment.phrase, Phrase::Plain(_)) {
+                continue;
+            }
+            let plain_end = self.pos - fragment.fragment.input_len();
+            if start == plain_end {
+                return Some(fragment);
+            }
+            self.pending = Some(fragment);
+            return Some(self.plain(start, plain_end));
+        }
+        (start < self.pos).then(|| self.plain(start, self.pos))
+    }
+}
+
+impl<I, S, DW> MergePlainIter<I, S, DW>
+where
+    S: Input<Item = char> + Copy,
+{
+    fn plain(&self, start: usize, end: usize) -> ParsedFragment<S, DW> {
+        let plain = self.text.take_from(start).take(end - start);
+        ParsedFragment::new(plain, Phrase::new_plain(PlainPhrase::new(plain)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NewLinePhrase, NewLineType};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn plain(text: &'static str) -> ParsedFragment<&'static str, ()> {
+        ParsedFragment::new(text, Phrase::new_plain(PlainPhrase::new(text)))
+    }
+
+    fn new_line() -> ParsedFragment<&'static str, ()> {
+        ParsedFragment::new(
+            "\n",
+            Phrase::new_new_line(NewLinePhrase::new(NewLineType::Lf)),
+        )
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(vec![], vec![])]
+    #[case(vec![plain("茶"), plain("屋")], vec![plain("茶屋")])]
+    #[case(vec![plain("茶"), new_line(), plain("屋")], vec![plain("茶"), new_line(), plain("屋")])]
+    #[case(vec![new_line(), plain("茶"), plain("屋"), new_line()], vec![new_line(), plain("茶屋"), new_line()])]
+    fn merge_plain_works(
+        #[case] fragments: Vec<ParsedFragment<&'static str, ()>>,
+        #[case] expected: Vec<ParsedFragment<&'static str, ()>>,
+    ) {
+        let text = fragments.iter().map(|f| *f.fragment()).collect::<String>();
+        assert_that!(
+            MergePlainIter::new(fragments.into_iter(), text.as_str()).collect::<Vec<_>>(),
+            eq(&expected)
+        );
+    }
+}
@@ -1,17 +1,53 @@
+mod annotation;
+#[cfg(feature = "async")]
+mod async_reader;
+pub(crate) mod auto_glossary;
 mod context_parser;
+mod dictionary_backend;
+pub(crate) mod first_chars;
 pub(crate) mod general_parser;
-mod nom_parsers;
+mod incremental;
+mod input;
+mod layered_dictionary;
+mod merge_plain;
+pub(crate) mod nom_parsers;
+#[cfg(feature = "parallel")]
+mod parallel;
 pub(crate) mod parse_dictionary;
+mod parse_options;
+mod parse_text;
+mod reader;
+#[cfg(feature = "ropey")]
+mod rope_input;
+mod search;
+#[cfg(test)]
+mod test_fixture;
+pub(crate) mod trie_view;
+mod updatable_dictionary;
+
+use std::borrow::Borrow;
 
+use crawdad::Trie;
 use derive_getters::Getters;
 use derive_new::new;
+pub use dictionary_backend::*;
 use general_parser::*;
-use nom::{AsBytes, Compare, Input};
+pub use incremental::*;
+pub use input::*;
+pub use parse_options::*;
+pub use parse_text::ParseText;
+#[cfg(feature = "ropey")]
+pub use rope_input::*;
+pub use search::*;
 use thiserror::Error;
 
 use crate::{
-    Phrase, PreparedDictionary, dictionary::DictionaryWord,
-    parser::context_parser::GeneralContextParser,
+    FormatVersionPolicy, MappedDictionary, Phrase, PlainPhrase, PreparedDictionary,
+    dictionary::DictionaryWord,
+    parser::{
+        layered_dictionary::LayeredDictionary, merge_plain::MergePlainIter,
+        parse_dictionary::DoubleArrayDictionary, updatable_dictionary::UpdatableDictionary,
+    },
 };
 
 #[derive(new, Error, Debug)]
@@ -21,46 +57,411 @@ pub enum Error {
 
     #[error("辞書シリアライズに失敗しました")]
     SerializeDictionary,
+
+    #[error("辞書の版が異なります(期待する版: {0}, 実際の版: {1})")]
+    FormatVersionMismatch(&'static str, String),
+
+    #[error("辞書が壊れています: {0}")]
+    CorruptedDictionary(&'static str),
+
+    #[error("見出しが空の語は登録できません")]
+    EmptyDictionaryKey,
+
+    #[error("辞書の語が不正です: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    InvalidDictionaryWords(Vec<crate::DictionaryIssue>),
+
+    #[error("辞書ファイルの読み込みに失敗しました")]
+    ReadDictionary(std::io::Error),
+
+    #[error("辞書ファイルの書き込みに失敗しました")]
+    WriteDictionary(std::io::Error),
+
+    #[error("メモリマップ辞書の形式が不正です: {0}")]
+    InvalidMappedDictionary(&'static str),
+
+    #[error("辞書の付加情報のシリアライズに失敗しました")]
+    SerializeDictionaryExtra(postcard::Error),
+
+    #[error("辞書の付加情報のデシリアライズに失敗しました")]
+    DeserializeDictionaryExtra(postcard::Error),
+
+    #[cfg(feature = "toml")]
+    #[error("辞書の{0}行目が不正です: {1}")]
+    InvalidDictionarySource(usize, String),
+
+    #[cfg(feature = "yomitan")]
+    #[error("Yomitan辞書アーカイブの展開に失敗しました")]
+    ReadYomitanArchive(zip::result::ZipError),
+
+    #[cfg(feature = "yomitan")]
+    #[error("Yomitan辞書の解析に失敗しました")]
+    ParseYomitanDictionary(serde_json::Error),
+
+    #[cfg(feature = "yomitan")]
+    #[error("Yomitan辞書の形式が不正です: {0}")]
+    InvalidYomitanTerm(String),
 }
 pub type Result<T> = core::result::Result<T, Error>;
 
-pub struct Parser<X = ()>(GeneralParser<DictionaryWord<X>>);
+/// 辞書を層として積み重ねて引くパーサー。一致の長い語を優先し、長さが同じなら後から積んだ層を優先する。
+/// 見出しは`B`の[`DictionaryBackend`]で引く
+pub struct Parser<X = (), B = Trie>(
+    GeneralParser<LayeredDictionary<DoubleArrayDictionary<DictionaryWord<X>, B>>>,
+);
 
 impl Default for Parser<()> {
     fn default() -> Self {
-        Self(GeneralParser::<DictionaryWord<()>>::default())
+        Self(GeneralParser::new(LayeredDictionary::default()))
     }
 }
 
-impl<X> TryFrom<PreparedDictionary<DictionaryWord<X>>> for Parser<X>
+impl<X, B> TryFrom<PreparedDictionary<DictionaryWord<X>, B>> for Parser<X, B>
 where
-    X: Clone,
+    X: Clone + serde::Serialize,
+    B: DictionaryBackend,
 {
     type Error = Error;
     fn try_from(
-        value: PreparedDictionary<DictionaryWord<X>>,
+        value: PreparedDictionary<DictionaryWord<X>, B>,
     ) -> std::result::Result<Self, Self::Error> {
-        Ok(Self(GeneralParser::try_from(value)?))
+        Self::try_from_prepared(value, FormatVersionPolicy::default())
+    }
+}
+
+impl<X, B> parse_text::sealed::HasGeneralParser for Parser<X, B> {
+    type Dictionary = LayeredDictionary<DoubleArrayDictionary<DictionaryWord<X>, B>>;
+    fn general_parser(&self) -> &GeneralParser<Self::Dictionary> {
+        &self.0
+    }
+}
+
+impl<X, B> Parser<X, B>
+where
+    X: Clone + serde::Serialize,
+    B: DictionaryBackend,
+{
+    pub fn try_from_prepared(
+        value: PreparedDictionary<DictionaryWord<X>, B>,
+        policy: FormatVersionPolicy,
+    ) -> Result<Self> {
+        Ok(Self(GeneralParser::new(LayeredDictionary::from(
+            DoubleArrayDictionary::try_from_prepared(value, policy)?,
+        ))))
+    }
+
+    /// 準備済みの辞書を最も優先する層として積み、その層の番号を返す
+    pub fn push_prepared_layer(
+        &mut self,
+        value: PreparedDictionary<DictionaryWord<X>, B>,
+        policy: FormatVersionPolicy,
+    ) -> Result<usize> {
+        Ok(self
+            .0
+            .dictionary_mut()
+            .push(DoubleArrayDictionary::try_from_prepared(value, policy)?))
     }
 }
 
 impl Parser<()> {
     pub fn try_new_with_dic<X>(words: impl Into<Vec<DictionaryWord<X>>>) -> Result<Parser<X>> {
-        Ok(Parser::<X>(
-            GeneralParser::<DictionaryWord<X>>::try_new_bytes_with_dic(words)?,
-        ))
+        Parser::try_new_with_layers([words])
+    }
+
+    /// [`Parser::try_new_with_dic`]の、語をイテレーターで受け取るもの。
+    /// 語は集めた並びのまま辞書に持つので、複製しない
+    pub fn try_new_with_dic_iter<X>(
+        words: impl IntoIterator<Item = DictionaryWord<X>>,
+    ) -> Result<Parser<X>> {
+        Parser::try_new_with_dic(words.into_iter().collect::<Vec<_>>())
+    }
+
+    /// 先に渡した辞書ほど下の層になる
+    pub fn try_new_with_layers<X, W>(layers: impl IntoIterator<Item = W>) -> Result<Parser<X>>
+    where
+        W: Into<Vec<DictionaryWord<X>>>,
+    {
+        Parser::try_new_with_backend(layers)
+    }
+
+    /// [`Parser::try_new_with_layers`]の、見出しを引く実装を選べるもの
+    pub fn try_new_with_backend<X, B, W>(
+        layers: impl IntoIterator<Item = W>,
+    ) -> Result<Parser<X, B>>
+    where
+        B: DictionaryBackend,
+        W: Into<Vec<DictionaryWord<X>>>,
+    {
+        Ok(Parser::<X, B>(GeneralParser::new(
+            layers
+                .into_iter()
+                .map(|words| DoubleArrayDictionary::try_new(words.into()))
+                .collect::<Result<_>>()?,
+        )))
     }
 }
 
-impl<X> Parser<X> {
-    pub fn parse_iter<S>(
+impl<X, B> Parser<X, B>
+where
+    B: DictionaryBackend,
+{
+    /// 辞書を最も優先する層として積み、その層の番号を返す
+    pub fn push_layer(&mut self, words: impl Into<Vec<DictionaryWord<X>>>) -> Result<usize> {
+        Ok(self
+            .0
+            .dictionary_mut()
+            .push(DoubleArrayDictionary::try_new(words.into())?))
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.0.dictionary().layers().len()
+    }
+
+    /// 見出しが完全に一致する語
+    pub fn get(&self, key: &str) -> Option<&DictionaryWord<X>> {
+        self.0.dictionary().get(key)
+    }
+
+    /// 見出しが`text`の先頭に一致する語
+    pub fn common_prefix_search(&self, text: &str, order: SearchOrder) -> Vec<&DictionaryWord<X>>
+    where
+        X: WordFrequency,
+    {
+        let mut words = self.0.dictionary().common_prefix_search(text);
+        sort_words(&mut words, order);
+        words
+    }
+
+    /// 見出しが`prefix`で始まる語
+    pub fn predictive_search(&self, prefix: &str, order: SearchOrder) -> Vec<&DictionaryWord<X>>
+    where
+        X: WordFrequency,
+    {
+        let mut words = self.0.dictionary().predictive_search(prefix);
+        sort_words(&mut words, order);
+        words
+    }
+
+    /// 読みが一致する語。片仮名と平仮名は区別しない
+    pub fn search_by_reading(&self, reading: &str, order: SearchOrder) -> Vec<&DictionaryWord<X>>
+    where
+        X: WordFrequency,
+    {
+        let mut words = self.0.dictionary().search_by_reading(reading);
+        sort_words(&mut words, order);
+        words
+    }
+
+    /// 読みが`prefix`で始まる語。片仮名と平仮名は区別しない
+    pub fn predictive_search_by_reading(
         &self,
-        text: S,
-    ) -> impl Iterator<Item = ParsedFragment<S, &DictionaryWord<X>>>
+        prefix: &str,
+        order: SearchOrder,
+    ) -> Vec<&DictionaryWord<X>>
+    where
+        X: WordFrequency,
+    {
+        let mut words = self.0.dictionary().predictive_search_by_reading(prefix);
+        sort_words(&mut words, order);
+        words
+    }
+
+    /// 語の説明文を本文と同じ規則で解析する。説明文中で一致した他の語は相互参照として返し、
+    /// 語自身への一致は前後の平文とまとめて平文にする
+    pub fn parse_description<'a>(
+        &'a self,
+        word: &'a DictionaryWord<X>,
+    ) -> impl Iterator<Item = ParsedFragment<&'a str, &'a DictionaryWord<X>>> {
+        let description = word.description().as_str();
+        let iter = self
+            .parse_iter(description)
+            .map(move |ParsedFragment { fragment, phrase }| {
+                let phrase = match phrase {
+                    Phrase::DictionaryWord(dw) if dw.word().key() == word.key() => {
+                        Phrase::new_plain(PlainPhrase::new(fragment))
+                    }
+                    phrase => phrase,
+                };
+                ParsedFragment { fragment, phrase }
+            });
+        MergePlainIter::new(iter, description)
+    }
+
+    /// 説明文から参照している他の語。初出順で重複は除く
+    pub fn cross_references<'a>(
+        &'a self,
+        word: &'a DictionaryWord<X>,
+    ) -> Vec<&'a DictionaryWord<X>> {
+        let mut references: Vec<&DictionaryWord<X>> = vec![];
+        for fragment in self.parse_description(word) {
+            if let Phrase::DictionaryWord(dw) = fragment.phrase
+                && !references.iter().any(|r| std::ptr::eq(*r, *dw.word()))
+            {
+                references.push(dw.word());
+            }
+        }
+        references
+    }
+
+    /// すべての層の語
+    pub(crate) fn words(&self) -> impl Iterator<Item = &DictionaryWord<X>> {
+        self.0
+            .dictionary()
+            .layers()
+            .iter()
+            .flat_map(|layer| layer.words())
+    }
+}
+
+/// [`MappedDictionary`]を引くパーサー。辞書の語は一致したときにだけ読み出す
+pub struct MappedParser<B = Vec<u8>, X = ()>(GeneralParser<MappedDictionary<B, X>>)
+where
+    B: AsRef<[u8]>;
+
+impl<B, X> From<MappedDictionary<B, X>> for MappedParser<B, X>
+where
+    B: AsRef<[u8]>,
+{
+    fn from(value: MappedDictionary<B, X>) -> Self {
+        Self(GeneralParser::new(value))
+    }
+}
+
+impl<B, X> parse_text::sealed::HasGeneralParser for MappedParser<B, X>
+where
+    B: AsRef<[u8]>,
+{
+    type Dictionary = MappedDictionary<B, X>;
+    fn general_parser(&self) -> &GeneralParser<Self::Dictionary> {
+        &self.0
+    }
+}
+
+impl MappedParser<Vec<u8>, ()> {
+    /// 語を[`MappedDictionary`]の形式に詰めてメモリ上に持つ。語ごとの割り当てが無く、
+    /// 見出しと区切りの表記は同じ文字列を指すので、[`Parser`]より小さい
+    pub fn try_new_with_dic<X>(words: &[DictionaryWord<X>]) -> Result<MappedParser<Vec<u8>, X>>
     where
-        S: Input<Item = char> + Copy + Compare<&'static str> + AsBytes,
+        X: serde::Serialize,
     {
-        self.0.parse_iter::<S, GeneralContextParser>(text)
+        Ok(MappedParser::from(MappedDictionary::prepare(words)?))
+    }
+
+    /// [`MappedParser::try_new_with_dic`]の、語をイテレーターで受け取るもの。
+    /// 語は1つずつ詰めて手放すので、語の並び全体を持たない
+    pub fn try_new_with_dic_iter<X, I, W>(words: I) -> Result<MappedParser<Vec<u8>, X>>
+    where
+        X: serde::Serialize,
+        I: IntoIterator<Item = W>,
+        W: Borrow<DictionaryWord<X>>,
+    {
+        Ok(MappedParser::from(MappedDictionary::prepare_iter(words)?))
+    }
+}
+
+impl<B, X> MappedParser<B, X>
+where
+    B: AsRef<[u8]>,
+{
+    pub fn try_from_bytes(bytes: B) -> Result<Self> {
+        Ok(Self::from(MappedDictionary::try_new(bytes)?))
+    }
+
+    pub fn dictionary(&self) -> &MappedDictionary<B, X> {
+        self.0.dictionary()
+    }
+}
+
+/// 語の追加・削除・置き換えができるパーサー。
+/// 変更は上書き領域に溜め、一定数を超えたら別スレッドでダブル配列を作り直すので、
+/// 変更のたびに辞書全体を作り直さずに済む
+pub struct UpdatableParser<X = ()>(GeneralParser<UpdatableDictionary<X>>);
+
+impl Default for UpdatableParser<()> {
+    fn default() -> Self {
+        Self(GeneralParser::new(UpdatableDictionary::from(
+            DoubleArrayDictionary::default(),
+        )))
+    }
+}
+
+impl<X> TryFrom<PreparedDictionary<DictionaryWord<X>>> for UpdatableParser<X>
+where
+    X: Clone + serde::Serialize,
+{
+    type Error = Error;
+    fn try_from(
+        value: PreparedDictionary<DictionaryWord<X>>,
+    ) -> std::result::Result<Self, Self::Error> {
+        Ok(Self(GeneralParser::new(UpdatableDictionary::from(
+            DoubleArrayDictionary::try_from(value)?,
+        ))))
+    }
+}
+
+impl<X> parse_text::sealed::HasGeneralParser for UpdatableParser<X> {
+    type Dictionary = UpdatableDictionary<X>;
+    fn general_parser(&self) -> &GeneralParser<Self::Dictionary> {
+        &self.0
+    }
+}
+
+impl UpdatableParser<()> {
+    pub fn try_new_with_dic<X>(
+        words: impl Into<Vec<DictionaryWord<X>>>,
+    ) -> Result<UpdatableParser<X>> {
+        Ok(UpdatableParser::<X>(GeneralParser::new(
+            UpdatableDictionary::from(DoubleArrayDictionary::try_new(words.into())?),
+        )))
+    }
+}
+
+impl<X> UpdatableParser<X> {
+    /// 上書き領域の語数がこの値に達したら畳み込みを始める
+    pub fn with_compaction_threshold(mut self, threshold: usize) -> Self {
+        self.0.dictionary_mut().set_compaction_threshold(threshold);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&DictionaryWord<X>> {
+        self.0.dictionary().get(key)
+    }
+
+    pub fn is_compacting(&self) -> bool {
+        self.0.dictionary().is_compacting()
+    }
+
+    /// まだダブル配列に畳み込まれていない変更の数
+    pub fn pending_changes(&self) -> usize {
+        self.0.dictionary().overlay_len()
+    }
+}
+
+impl<X> UpdatableParser<X>
+where
+    X: Clone + Send + 'static,
+{
+    /// 見出しが既にあれば何もせず`false`を返す
+    pub fn insert(&mut self, word: DictionaryWord<X>) -> Result<bool> {
+        self.0.dictionary_mut().insert(word)
+    }
+
+    /// 見出しが同じ語を置き換え、元の語を返す。無ければ追加する
+    pub fn replace(&mut self, word: DictionaryWord<X>) -> Result<Option<DictionaryWord<X>>> {
+        self.0.dictionary_mut().replace(word)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Result<Option<DictionaryWord<X>>> {
+        self.0.dictionary_mut().remove(key)
+    }
+
+    /// 別スレッドでの畳み込みが終わっていれば取り込み、`true`を返す
+    pub fn poll_compaction(&mut self) -> Result<bool> {
+        self.0.dictionary_mut().poll_compaction()
+    }
+
+    /// 溜まった変更をすべて畳み込むまで待つ
+    pub fn compact(&mut self) -> Result<()> {
+        self.0.dictionary_mut().compact()
     }
 }
 
@@ -71,15 +472,38 @@ pub struct ParsedFragment<S, DW> {
     phrase: Phrase<S, DW>,
 }
 
+impl<S, DW> ParsedFragment<S, DW> {
+    /// 本文を指す部分を`f`で変換する
+    pub fn map_text<T>(self, f: impl Fn(S) -> T) -> ParsedFragment<T, DW> {
+        ParsedFragment {
+            fragment: f(self.fragment),
+            phrase: self.phrase.map_text(f),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::{DictionaryPhrase, NewLinePhrase, PlainPhrase, RubyPhrase, RubyType};
+    use crate::{
+        DictionaryPhrase, DictionaryWordKeyPhrase, NewLinePhrase, PlainPhrase, RubyPhrase, RubyType,
+    };
 
     use super::*;
     use googletest::prelude::*;
     use rstest::*;
 
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn parsers_are_send_and_sync() {
+        assert_send_sync::<Parser>();
+        assert_send_sync::<Parser<(), HashMapBackend>>();
+        assert_send_sync::<MappedParser>();
+        assert_send_sync::<MappedParser<&[u8]>>();
+        assert_send_sync::<UpdatableParser>();
+    }
+
     #[fixture]
     fn words() -> Vec<DictionaryWord> {
         vec![DictionaryWord::new(
@@ -153,4 +577,421 @@ mod tests {
         assert_that!(actual, eq(&expected));
         Ok(())
     }
+
+    #[gtest]
+    fn parse_with_mapped_dic() -> anyhow::Result<()> {
+        let text = include_str!("test_data/parse_with_dic/case1.txt");
+        let dic = MappedDictionary::prepare(&words())?;
+        let parser = MappedParser::try_from_bytes(dic.as_bytes())?;
+        let word = parser.dictionary().word(0).unwrap();
+        let expected = vec![
+            ParsedFragment::new(
+                "大砲",
+                Phrase::new_dictionary_word(DictionaryPhrase::new("大砲", word)),
+            ),
+            ParsedFragment::new("を撃て", Phrase::new_plain(PlainPhrase::new("を撃て"))),
+            ParsedFragment::new(
+                "\n",
+                Phrase::new_new_line(NewLinePhrase::new(crate::NewLineType::Lf)),
+            ),
+            ParsedFragment::new(
+                "|大砲(たいほう)",
+                Phrase::new_ruby(RubyPhrase::new("大砲", "たいほう", RubyType::Instruction)),
+            ),
+            ParsedFragment::new(
+                "\n",
+                Phrase::new_new_line(NewLinePhrase::new(crate::NewLineType::Lf)),
+            ),
+        ];
+        assert_that!(parser.parse_iter(text).collect::<Vec<_>>(), eq(&expected));
+        assert_that!(word.to_word()?, eq(&words()[0]));
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_with_compact_dic() -> anyhow::Result<()> {
+        let text = include_str!("test_data/parse_with_dic/case1.txt");
+        let reference = Parser::try_new_with_dic(words())?;
+        let expected = reference
+            .parse_iter(text)
+            .map(|f| match f.phrase() {
+                Phrase::DictionaryWord(dw) => {
+                    (f.fragment().to_string(), Some((*dw.word()).clone()))
+                }
+                _ => (f.fragment().to_string(), None),
+            })
+            .collect::<Vec<_>>();
+        let parser = MappedParser::try_new_with_dic(&words())?;
+        let actual = parser
+            .parse_iter(text)
+            .map(|f| match f.phrase() {
+                Phrase::DictionaryWord(dw) => {
+                    Ok((f.fragment().to_string(), Some(dw.word().to_word()?)))
+                }
+                _ => Ok((f.fragment().to_string(), None)),
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        assert_that!(actual, eq(&expected));
+        Ok(())
+    }
+
+    #[gtest]
+    fn try_new_with_dic_iter_works() -> anyhow::Result<()> {
+        let text = include_str!("test_data/parse_with_dic/case1.txt");
+        let parser = Parser::try_new_with_dic_iter(words())?;
+        assert_that!(
+            parser.parse_iter(text).collect::<Vec<_>>(),
+            eq(&Parser::try_new_with_dic(words())?
+                .parse_iter(text)
+                .collect::<Vec<_>>())
+        );
+        let mapped = MappedParser::try_new_with_dic_iter(words())?;
+        assert_that!(
+            mapped.dictionary().as_bytes(),
+            eq(MappedParser::try_new_with_dic(&words())?
+                .dictionary()
+                .as_bytes())
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_with_memory_mapped_dic() -> anyhow::Result<()> {
+        let text = include_str!("test_data/parse_with_dic/case1.txt");
+        let path = std::env::temp_dir().join(format!(
+            "jp-web-novel-text-parse-with-memory-mapped-dic-{}.dic",
+            std::process::id()
+        ));
+        std::fs::write(&path, MappedDictionary::prepare(&words())?.as_bytes())?;
+        let file = std::fs::File::open(&path)?;
+        // SAFETY: テスト中はファイルを書き換えない
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        let parser = MappedParser::<_, ()>::try_from_bytes(mmap)?;
+        let expected = Parser::try_new_with_dic(words())?
+            .parse_iter(text)
+            .map(|f| f.fragment().to_string())
+            .collect::<Vec<_>>();
+        let actual = parser
+            .parse_iter(text)
+            .map(|f| f.fragment().to_string())
+            .collect::<Vec<_>>();
+        std::fs::remove_file(&path)?;
+        assert_that!(actual, eq(&expected));
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_with_hash_map_backend() -> anyhow::Result<()> {
+        let text = include_str!("test_data/parse_with_dic/case1.txt");
+        let expected = Parser::try_new_with_dic(words())?
+            .parse_iter(text)
+            .map(|f| f.fragment().to_string())
+            .collect::<Vec<_>>();
+        let parser = Parser::try_new_with_backend::<_, HashMapBackend, _>([words()])?;
+        let prepared =
+            Parser::<_, HashMapBackend>::try_from(
+                PreparedDictionary::<_, HashMapBackend>::prepare_with_backend(words())?,
+            )?;
+        for parser in [parser, prepared] {
+            let actual = parser
+                .parse_iter(text)
+                .map(|f| f.fragment().to_string())
+                .collect::<Vec<_>>();
+            assert_that!(actual, eq(&expected));
+            assert_that!(parser.get("大砲"), some(eq(&words()[0])));
+        }
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_with_layers() -> anyhow::Result<()> {
+        let house = vec![
+            DictionaryWord::new("魔導".into(), "まどう".into(), "共通".into()),
+            DictionaryWord::new("魔導書".into(), "まどうしょ".into(), "共通".into()),
+        ];
+        let work = vec![DictionaryWord::new(
+            "魔導".into(),
+            "マドウ".into(),
+            "作品".into(),
+        )];
+        let mut parser = Parser::try_new_with_dic(house.clone())?;
+        assert_that!(parser.push_layer(work.clone())?, eq(1));
+        assert_that!(parser.layer_count(), eq(2));
+        let expected = vec![
+            ParsedFragment::new(
+                "魔導",
+                Phrase::new_dictionary_word(DictionaryPhrase::new("魔導", &work[0]).with_layer(1)),
+            ),
+            ParsedFragment::new("と", Phrase::new_plain(PlainPhrase::new("と"))),
+            ParsedFragment::new(
+                "魔導書",
+                Phrase::new_dictionary_word(DictionaryPhrase::new("魔導書", &house[1])),
+            ),
+        ];
+        assert_that!(
+            parser.parse_iter("魔導と魔導書").collect::<Vec<_>>(),
+            eq(&expected)
+        );
+
+        let parser = Parser::try_new_with_layers([work.clone(), house.clone()])?;
+        assert_that!(
+            parser
+                .parse_iter("魔導")
+                .map(|f| f.phrase().clone())
+                .collect::<Vec<_>>(),
+            eq(&vec![Phrase::new_dictionary_word(
+                DictionaryPhrase::new("魔導", &house[0]).with_layer(1)
+            )])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_with_updatable_dic() -> anyhow::Result<()> {
+        let mut parser = UpdatableParser::try_new_with_dic(words())?;
+        let word = DictionaryWord::new("撃て".into(), "うて".into(), "bar".into());
+        assert_that!(parser.insert(word.clone())?, eq(true));
+        parser.remove("大砲")?;
+        let expected = vec![
+            ParsedFragment::new("大砲を", Phrase::new_plain(PlainPhrase::new("大砲を"))),
+            ParsedFragment::new(
+                "撃て",
+                Phrase::new_dictionary_word(DictionaryPhrase::new("撃て", &word)),
+            ),
+        ];
+        assert_that!(
+            parser.parse_iter("大砲を撃て").collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        parser.compact()?;
+        assert_that!(
+            parser.parse_iter("大砲を撃て").collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        Ok(())
+    }
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Frequency(i64);
+
+    impl WordFrequency for Frequency {
+        fn frequency(&self) -> i64 {
+            self.0
+        }
+    }
+
+    fn search_words() -> Vec<DictionaryWord<Frequency>> {
+        [("好奇", 1), ("好奇心", 3), ("好奇心旺盛", 2), ("茶屋", 5)]
+            .into_iter()
+            .map(|(key, frequency)| {
+                DictionaryWord::new_all(
+                    vec![DictionaryWordKeyPhrase::new_plain(key.into())],
+                    "".into(),
+                    Frequency(frequency),
+                )
+            })
+            .collect()
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("好奇心旺盛な", SearchOrder::KeyLength, vec!["好奇", "好奇心", "好奇心旺盛"])]
+    #[case("好奇心旺盛な", SearchOrder::Frequency, vec!["好奇心", "好奇心旺盛", "好奇"])]
+    #[case("茶屋で", SearchOrder::KeyLength, vec!["茶屋"])]
+    #[case("好", SearchOrder::KeyLength, vec![])]
+    fn common_prefix_search_works(
+        #[case] text: &str,
+        #[case] order: SearchOrder,
+        #[case] expected: Vec<&str>,
+    ) -> anyhow::Result<()> {
+        let parser = Parser::try_new_with_dic(search_words())?;
+        assert_that!(
+            parser
+                .common_prefix_search(text, order)
+                .iter()
+                .map(|w| w.key().as_str())
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("好奇", SearchOrder::KeyLength, vec!["好奇", "好奇心", "好奇心旺盛"])]
+    #[case("好奇", SearchOrder::Frequency, vec!["好奇心", "好奇心旺盛", "好奇"])]
+    #[case("好奇心", SearchOrder::KeyLength, vec!["好奇心", "好奇心旺盛"])]
+    #[case("", SearchOrder::Frequency, vec!["茶屋", "好奇心", "好奇心旺盛", "好奇"])]
+    #[case("魔", SearchOrder::KeyLength, vec![])]
+    fn predictive_search_works(
+        #[case] prefix: &str,
+        #[case] order: SearchOrder,
+        #[case] expected: Vec<&str>,
+    ) -> anyhow::Result<()> {
+        let parser = Parser::try_new_with_dic(search_words())?;
+        assert_that!(
+            parser
+                .predictive_search(prefix, order)
+                .iter()
+                .map(|w| w.key().as_str())
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn search_prefers_upper_layer() -> anyhow::Result<()> {
+        let parser = Parser::try_new_with_layers([
+            vec![
+                DictionaryWord::new("茶屋".into(), "ちゃや".into(), "共通".into()),
+                DictionaryWord::new("茶".into(), "ちゃ".into(), "共通".into()),
+            ],
+            vec![DictionaryWord::new(
+                "茶屋".into(),
+                "ちゃや".into(),
+                "作品".into(),
+            )],
+        ])?;
+        assert_that!(
+            parser.get("茶屋").map(|w| w.description().as_str()),
+            some(eq("作品"))
+        );
+        assert_that!(parser.get("茶店"), none());
+        assert_that!(
+            parser
+                .predictive_search("茶", SearchOrder::KeyLength)
+                .iter()
+                .map(|w| (w.key().as_str(), w.description().as_str()))
+                .collect::<Vec<_>>(),
+            eq(&vec![("茶", "共通"), ("茶屋", "作品")])
+        );
+        assert_that!(
+            parser
+                .common_prefix_search("茶屋で", SearchOrder::KeyLength)
+                .iter()
+                .map(|w| (w.key().as_str(), w.description().as_str()))
+                .collect::<Vec<_>>(),
+            eq(&vec![("茶", "共通"), ("茶屋", "作品")])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn search_by_reading_works() -> anyhow::Result<()> {
+        let words = vec![
+            DictionaryWord::new("大砲".into(), "たいほう".into(), "".into()),
+            DictionaryWord::new("退歩".into(), "タイホ".into(), "".into()),
+            DictionaryWord::new("魔導".into(), "まどう".into(), "".into()),
+        ];
+        for parser in [
+            Parser::try_new_with_dic(words.clone())?,
+            Parser::try_from(PreparedDictionary::prepare(words.clone())?)?,
+        ] {
+            assert_that!(
+                parser
+                    .search_by_reading("タイホウ", SearchOrder::KeyLength)
+                    .iter()
+                    .map(|w| w.key().as_str())
+                    .collect::<Vec<_>>(),
+                eq(&vec!["大砲"])
+            );
+            assert_that!(
+                parser
+                    .predictive_search_by_reading("たいほ", SearchOrder::KeyLength)
+                    .iter()
+                    .map(|w| w.key().as_str())
+                    .collect::<Vec<_>>(),
+                eq(&vec!["大砲", "退歩"])
+            );
+            assert_that!(
+                parser.search_by_reading("たいほ", SearchOrder::KeyLength),
+                elements_are![predicate(|w: &&DictionaryWord| w.key() == "退歩")]
+            );
+        }
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_description_works() -> anyhow::Result<()> {
+        let words = vec![
+            DictionaryWord::new(
+                "茶屋".into(),
+                "ちゃや".into(),
+                "|製茶《せいちゃ》を売る店。茶屋は茶舗とも。".into(),
+            ),
+            DictionaryWord::new("茶舗".into(), "ちゃほ".into(), "茶屋に同じ。".into()),
+        ];
+        let parser = Parser::try_new_with_dic(words.clone())?;
+        let (chaya, chaho) = (parser.get("茶屋").unwrap(), parser.get("茶舗").unwrap());
+        assert_that!(
+            parser
+                .parse_description(chaya)
+                .map(|f| f.phrase().clone())
+                .collect::<Vec<_>>(),
+            eq(&vec![
+                Phrase::new_ruby(RubyPhrase::new("製茶", "せいちゃ", RubyType::Instruction)),
+                Phrase::new_plain(PlainPhrase::new("を売る店。茶屋は")),
+                Phrase::new_dictionary_word(DictionaryPhrase::new("茶舗", chaho)),
+                Phrase::new_plain(PlainPhrase::new("とも。")),
+            ])
+        );
+        assert_that!(parser.cross_references(chaya), eq(&vec![chaho]));
+        assert_that!(parser.cross_references(chaho), eq(&vec![chaya]));
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(RubyLimits::default(), vec![("素玄人《しろうと》", true)])]
+    #[case(RubyLimits::new(2, 64), vec![("素玄人《しろうと》", false)])]
+    #[case(RubyLimits::new(3, 3), vec![("素玄人《しろうと》", false)])]
+    fn parse_with_ruby_limits(#[case] limits: RubyLimits, #[case] expected: Vec<(&str, bool)>) {
+        let parser = Parser::default();
+        let options = ParseOptions::default().with_ruby_limits(limits);
+        assert_that!(
+            parser
+                .parse_iter_with_options("素玄人《しろうと》", &options)
+                .map(|f| (*f.fragment(), matches!(f.phrase(), Phrase::Ruby(_))))
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
+    }
+
+    #[gtest]
+    fn kanji_ruby_starts_right_after_dictionary_word() -> anyhow::Result<()> {
+        let parser = Parser::try_new_with_dic(vec![DictionaryWord::new(
+            "お茶".into(),
+            "".into(),
+            "".into(),
+        )])?;
+        let expected = vec![("お茶", false), ("屋《ちゃや》", true)];
+        assert_that!(
+            parser
+                .parse_iter("お茶屋《ちゃや》")
+                .map(|f| (*f.fragment(), matches!(f.phrase(), Phrase::Ruby(_))))
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        assert_that!(
+            parser
+                .parse_iter_with_options("お茶屋《ちゃや》", &ParseOptions::default())
+                .map(|f| (*f.fragment(), matches!(f.phrase(), Phrase::Ruby(_))))
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_iter_has_no_ruby_limits() {
+        let text = "素".repeat(70) + "《もと》";
+        assert_that!(
+            Parser::default()
+                .parse_iter(text.as_str())
+                .map(|f| matches!(f.phrase(), Phrase::Ruby(_)))
+                .collect::<Vec<_>>(),
+            eq(&vec![true])
+        );
+    }
 }
@@ -1,4 +1,4 @@
-mod char;
+pub(crate) mod char;
 mod new_line;
 mod ruby;
 mod white_space;
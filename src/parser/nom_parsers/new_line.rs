@@ -2,7 +2,7 @@ use nom::{Compare, IResult, Input, Parser, bytes::complete::tag};
 
 use crate::{NewLinePhrase, NewLineType, Phrase, parser::ParsedFragment};
 
-pub(crate) fn new_line<'a, S, DW>(input: S) -> IResult<S, ParsedFragment<S, &'a DW>>
+pub(crate) fn new_line<S, DW>(input: S) -> IResult<S, ParsedFragment<S, DW>>
 where
     S: Input<Item = char> + Compare<&'static str> + Copy,
 {
@@ -49,6 +49,6 @@ mod tests {
         #[case] input: &str,
         #[case] expected: IResult<&str, ParsedFragment<&str, &DictionaryWord>>,
     ) {
-        assert_that!(new_line::<_, DictionaryWord>(input), eq(&expected));
+        assert_that!(new_line::<_, &DictionaryWord>(input), eq(&expected));
     }
 }
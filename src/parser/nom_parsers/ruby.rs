@@ -1,14 +1,14 @@
 use nom::{
     IResult, Input, Parser,
-    bytes::complete::{take_till, take_till1, take_while, take_while_m_n},
-    multi::many1_count,
+    bytes::complete::{take_while, take_while_m_n},
+    error::ErrorKind,
     sequence::{delimited, preceded},
 };
 
 use crate::{
     Phrase, RubyPhrase, RubyType,
     parser::{
-        ParsedFragment,
+        ParsedFragment, RubyLimits,
         nom_parsers::char::{
             is_end_ruby, is_ideographic_variation_sequence, is_kanji, is_new_line_escape,
             is_start_instruction, is_start_ruby,
@@ -16,16 +16,22 @@ use crate::{
     },
 };
 
-pub(crate) fn ruby_instruction<'a, S, DW>(input: S) -> IResult<S, ParsedFragment<S, &'a DW>>
+/// `|`で始まるルビ。親文字とルビが`limits`を超える長さなら一致しない
+pub(crate) fn ruby_instruction<S, DW>(
+    input: S,
+    limits: &RubyLimits,
+) -> IResult<S, ParsedFragment<S, DW>>
 where
     S: Input<Item = char> + Copy,
 {
     let (next, (target, ruby)) = (
         preceded(
             take_while_m_n(1, 1, is_start_instruction),
-            take_till1(|c| is_start_ruby(c) || is_new_line_escape(c)),
+            take_while_m_n(1, *limits.max_target_chars(), |c| {
+                !is_start_ruby(c) && !is_new_line_escape(c)
+            }),
         ),
-        ruby,
+        |i| ruby(i, limits),
     )
         .parse(input)?;
     let fragment = input.take(input.input_len() - next.input_len());
@@ -38,25 +44,28 @@ where
     ))
 }
 
-fn ruby<S>(input: S) -> IResult<S, S>
+fn ruby<S>(input: S, limits: &RubyLimits) -> IResult<S, S>
 where
     S: Input<Item = char> + Copy,
 {
     delimited(
         take_while_m_n(1, 1, is_start_ruby),
-        take_till(|c| is_end_ruby(c) || is_new_line_escape(c)),
+        take_while_m_n(0, *limits.max_ruby_chars(), |c| {
+            !is_end_ruby(c) && !is_new_line_escape(c)
+        }),
         take_while_m_n(1, 1, is_end_ruby),
     )
     .parse(input)
 }
 
-pub(crate) fn kanji_ruby<'a, S, DW>(input: S) -> IResult<S, ParsedFragment<S, &'a DW>>
+/// 漢字の後に続くルビ。漢字とルビが`limits`を超える長さなら一致しない
+pub(crate) fn kanji_ruby<S, DW>(input: S, limits: &RubyLimits) -> IResult<S, ParsedFragment<S, DW>>
 where
     S: Input<Item = char> + Copy,
 {
-    let (next_input, _) = many1_count(kanji).parse(input)?;
+    let next_input = kanji_run(input, *limits.max_target_chars())?;
     let kanji = input.take(input.input_len() - next_input.input_len());
-    let (r, ruby) = ruby.parse(next_input)?;
+    let (r, ruby) = ruby(next_input, limits)?;
     Ok((
         r,
         ParsedFragment::new(
@@ -66,6 +75,34 @@ where
     ))
 }
 
+/// 1文字以上`max`文字以下の漢字の並びの残り。
+/// 並びが`max`文字より長ければ、並びの終わりを指す[`ErrorKind::TooLarge`]の`Failure`を返す。
+/// 親文字の一部にだけルビを付けないよう、途中で打ち切らない
+fn kanji_run<S>(input: S, max: usize) -> Result<S, nom::Err<nom::error::Error<S>>>
+where
+    S: Input<Item = char> + Copy,
+{
+    let mut rest = input;
+    let mut count = 0;
+    while let Ok((next, _)) = kanji(rest) {
+        rest = next;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            ErrorKind::Many1Count,
+        )));
+    }
+    if count > max {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            rest,
+            ErrorKind::TooLarge,
+        )));
+    }
+    Ok(rest)
+}
+
 fn kanji<S>(input: S) -> IResult<S, S>
 where
     S: Input<Item = char> + Copy,
@@ -84,7 +121,7 @@ mod tests {
 
     use super::*;
     use googletest::prelude::*;
-    use nom::error;
+    use nom::{branch::alt, error};
     use rstest::*;
 
     #[gtest]
@@ -117,7 +154,10 @@ mod tests {
         #[case] input: &str,
         #[case] expected: IResult<&str, ParsedFragment<&str, &DictionaryWord>>,
     ) {
-        assert_that!(ruby_instruction(input), eq(&expected))
+        assert_that!(
+            ruby_instruction(input, &RubyLimits::unlimited()),
+            eq(&expected)
+        )
     }
 
     #[gtest]
@@ -153,7 +193,40 @@ mod tests {
         #[case] input: &str,
         #[case] expected: IResult<&str, ParsedFragment<&str, &DictionaryWord>>,
     ) {
-        assert_that!(kanji_ruby(input), eq(&expected))
+        assert_that!(kanji_ruby(input, &RubyLimits::unlimited()), eq(&expected))
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("|玄人(くろうと)", true)]
+    #[case("|玄人の技(くろうと)", false)]
+    #[case("|玄人(くろうとの)", false)]
+    #[case("玄人(くろうと)", true)]
+    #[case("玄人芸(くろうと)", true)]
+    #[case("玄人芸術(くろうと)", false)]
+    #[case("玄人(くろうとの)", false)]
+    fn ruby_respects_limits(#[case] input: &str, #[case] expected_ok: bool) {
+        let limits = RubyLimits::new(3, 4);
+        assert_that!(
+            alt((
+                |i| ruby_instruction::<_, ()>(i, &limits),
+                |i| kanji_ruby(i, &limits)
+            ))
+            .parse(input)
+            .is_ok(),
+            eq(expected_ok)
+        );
+    }
+
+    #[gtest]
+    fn too_long_kanji_run_fails_at_its_end() {
+        assert_that!(
+            kanji_ruby::<_, ()>("素玄人芸術(くろうと)", &RubyLimits::new(3, 4)),
+            eq(&Err(nom::Err::Failure(error::Error::new(
+                "(くろうと)",
+                error::ErrorKind::TooLarge
+            ))))
+        );
     }
 
     #[gtest]
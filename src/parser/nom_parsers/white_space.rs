@@ -7,7 +7,7 @@ use crate::{
         nom_parsers::char::{is_space, is_tab, is_zenkaku_space},
     },
 };
-pub(crate) fn space<'a, S, DW>(input: S) -> IResult<S, ParsedFragment<S, &'a DW>>
+pub(crate) fn space<S, DW>(input: S) -> IResult<S, ParsedFragment<S, DW>>
 where
     S: Input<Item = char> + Compare<&'static str> + Copy,
 {
@@ -20,7 +20,7 @@ where
     .parse(input)
 }
 
-pub(crate) fn zenkaku_space<'a, S, DW>(input: S) -> IResult<S, ParsedFragment<S, &'a DW>>
+pub(crate) fn zenkaku_space<S, DW>(input: S) -> IResult<S, ParsedFragment<S, DW>>
 where
     S: Input<Item = char> + Compare<&'static str> + Copy,
 {
@@ -36,7 +36,7 @@ where
     .parse(input)
 }
 
-pub(crate) fn tab<'a, S, DW>(input: S) -> IResult<S, ParsedFragment<S, &'a DW>>
+pub(crate) fn tab<S, DW>(input: S) -> IResult<S, ParsedFragment<S, DW>>
 where
     S: Input<Item = char> + Compare<&'static str> + Copy,
 {
@@ -79,7 +79,7 @@ mod tests {
         #[case] input: &str,
         #[case] expected: IResult<&str, ParsedFragment<&str, &DictionaryWord>>,
     ) {
-        assert_that!(space::<_, DictionaryWord>(input), eq(&expected));
+        assert_that!(space::<_, &DictionaryWord>(input), eq(&expected));
     }
 
     #[gtest]
@@ -103,7 +103,7 @@ mod tests {
         #[case] input: &str,
         #[case] expected: IResult<&str, ParsedFragment<&str, &DictionaryWord>>,
     ) {
-        assert_that!(zenkaku_space::<_, DictionaryWord>(input), eq(&expected));
+        assert_that!(zenkaku_space::<_, &DictionaryWord>(input), eq(&expected));
     }
 
     #[gtest]
@@ -127,6 +127,6 @@ mod tests {
         #[case] input: &str,
         #[case] expected: IResult<&str, ParsedFragment<&str, &DictionaryWord>>,
     ) {
-        assert_that!(tab::<_, DictionaryWord>(input), eq(&expected));
+        assert_that!(tab::<_, &DictionaryWord>(input), eq(&expected));
     }
 }
@@ -0,0 +1,93 @@
+use rayon::prelude::*;
+
+use crate::parser::{
+    ParsedFragment, context_parser::ContextParser, general_parser::GeneralParser,
+    general_parser::WordLookup,
+};
+
+/// 1つの塊の最小のバイト数。これより細かく分けても、スレッドを渡す手間の方が大きい
+const MIN_CHUNK_BYTES: usize = 16 * 1024;
+
+/// `text`を改行の直後で区切り、およそ`chunk_bytes`ずつの塊に分ける
+fn split_at_new_lines(text: &str, chunk_bytes: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = text;
+    while rest.len() > chunk_bytes {
+        // 改行は1バイトなので、見つけた位置の直後は必ず文字の境目になる
+        let Some(end) = rest.as_bytes()[chunk_bytes..]
+            .iter()
+            .position(|&b| b == b'\n')
+        else {
+            break;
+        };
+        let (chunk, next) = rest.split_at(chunk_bytes + end + 1);
+        chunks.push(chunk);
+        rest = next;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// 改行で区切った塊をrayonのスレッドで別々に解析してつなぐ。ルビは行をまたがないので、
+/// 改行を含む見出しの語がなければ、結果は[`GeneralParser::parse_iter`]で全体を解析したものと同じ
+pub(crate) fn par_parse<'a, 't, D, CP>(
+    parser: &'a GeneralParser<D>,
+    text: &'t str,
+    context_parser: CP,
+) -> Vec<ParsedFragment<&'t str, D::Word>>
+where
+    D: WordLookup<'a> + Sync,
+    D::Word: Send,
+    CP: for<'s> ContextParser<&'s str, D::Word> + Clone + Send + Sync,
+{
+    let chunk_bytes = MIN_CHUNK_BYTES.max(text.len() / (rayon::current_num_threads() * 4));
+    split_at_new_lines(text, chunk_bytes)
+        .into_par_iter()
+        .map(|chunk| {
+            parser
+                .parse_iter(chunk, context_parser.clone())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ParseText,
+        parser::test_fixture::{BROKEN_RUBY, KOKORO, SAMPLE, parser},
+    };
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    #[rstest]
+    #[case("", 1, vec![])]
+    #[case("あ\nい\nう", 1, vec!["あ\n", "い\n", "う"])]
+    #[case("あ\nい\nう\n", 4, vec!["あ\nい\n", "う\n"])]
+    #[case("あいう\n", 1, vec!["あいう\n"])]
+    #[case("あいう", 1, vec!["あいう"])]
+    fn split(#[case] text: &str, #[case] chunk_bytes: usize, #[case] expected: Vec<&str>) {
+        assert_that!(split_at_new_lines(text, chunk_bytes), eq(&expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(SAMPLE)]
+    #[case(BROKEN_RUBY)]
+    #[case(KOKORO)]
+    fn par_parse_matches_parse_iter(#[case] text: &str) {
+        let parser = parser();
+        assert_that!(
+            parser.par_parse(text),
+            eq(&parser.parse_iter(text).collect::<Vec<_>>())
+        );
+    }
+}
@@ -1,92 +1,192 @@
+use std::sync::OnceLock;
+
 use crawdad::Trie;
 use nom::Input;
 
 use crate::{
-    PreparedDictionary,
-    parser::{DictionaryWordContainer, Error, Result},
+    FormatVersionPolicy, PreparedDictionary,
+    dictionary::reading_index::ReadingIndex,
+    parser::{
+        DictionaryBackend, DictionaryWordContainer, Error, Result, WordLookup,
+        first_chars::FirstChars,
+    },
 };
 
-pub struct DoubleArrayDictionary<WD>
+/// 語の並びと、見出しから語の番号を引く[`DictionaryBackend`]の組
+pub struct DoubleArrayDictionary<WD, B = Trie>
 where
     WD: DictionaryWordContainer,
 {
     words: Vec<WD>,
-    trie: Option<Trie>,
+    backend: Option<B>,
+    /// 見出し順に並べた語の番号。前方一致検索で初めて使うときに作る
+    sorted: OnceLock<Vec<u32>>,
+    /// 読みの索引。読みで初めて引くときに作るか、[`PreparedDictionary`]から受け取る
+    readings: OnceLock<ReadingIndex>,
+    /// 見出しの先頭の文字。解析で初めて使うときに作る
+    first_chars: OnceLock<FirstChars>,
 }
 
-impl<WD> Default for DoubleArrayDictionary<WD>
+impl<WD, B> Default for DoubleArrayDictionary<WD, B>
 where
     WD: DictionaryWordContainer,
 {
     fn default() -> Self {
         Self {
             words: vec![],
-            trie: None,
+            backend: None,
+            sorted: OnceLock::new(),
+            readings: OnceLock::new(),
+            first_chars: OnceLock::new(),
         }
     }
 }
 
-impl<WD> TryFrom<PreparedDictionary<WD>> for DoubleArrayDictionary<WD>
+impl<WD, B> TryFrom<PreparedDictionary<WD, B>> for DoubleArrayDictionary<WD, B>
 where
     WD: Clone + DictionaryWordContainer,
+    WD::Extra: serde::Serialize,
+    B: DictionaryBackend,
 {
     type Error = Error;
-    fn try_from(value: PreparedDictionary<WD>) -> std::result::Result<Self, Self::Error> {
-        if value.format_version() != PreparedDictionary::<WD>::CURRENT_FORMAT_VERSION {
-            Self::try_new(value.words)
+    fn try_from(value: PreparedDictionary<WD, B>) -> std::result::Result<Self, Self::Error> {
+        Self::try_from_prepared(value, FormatVersionPolicy::default())
+    }
+}
+
+impl<WD, B> DoubleArrayDictionary<WD, B>
+where
+    WD: Clone + DictionaryWordContainer,
+    WD::Extra: serde::Serialize,
+    B: DictionaryBackend,
+{
+    pub fn try_from_prepared(
+        value: PreparedDictionary<WD, B>,
+        policy: FormatVersionPolicy,
+    ) -> Result<Self> {
+        let value = if value.format_version() == PreparedDictionary::<WD, B>::CURRENT_FORMAT_VERSION
+        {
+            value.verify()?;
+            value
         } else {
-            let (trie, _) = Trie::deserialize_from_slice(&value.trie_vec);
-            Ok(Self {
-                words: value.words,
-                trie: Some(trie),
-            })
-        }
+            match policy {
+                FormatVersionPolicy::Rebuild => return Self::try_new(value.words),
+                FormatVersionPolicy::Error => return Err(value.version_mismatch()),
+                FormatVersionPolicy::Migrate => value.migrate()?,
+            }
+        };
+        let backend = B::deserialize(&value.trie_vec, &value.words)?;
+        Ok(Self {
+            words: value.words,
+            backend: Some(backend),
+            sorted: OnceLock::new(),
+            readings: OnceLock::from(value.reading_index),
+            first_chars: OnceLock::new(),
+        })
     }
 }
 
-impl<WD> DoubleArrayDictionary<WD>
+impl<WD, B> DoubleArrayDictionary<WD, B>
 where
     WD: DictionaryWordContainer,
+    B: DictionaryBackend,
 {
     pub fn try_new(words: Vec<WD>) -> Result<Self> {
         if words.is_empty() {
-            Ok(Self { words, trie: None })
+            Ok(Self::default())
         } else {
-            let trie = Trie::from_keys(words.iter().map(|w| w.word().key()))
-                .map_err(Error::new_create_dictionary)?;
+            let backend = B::build(&words)?;
             Ok(Self {
                 words,
-                trie: Some(trie),
+                backend: Some(backend),
+                sorted: OnceLock::new(),
+                readings: OnceLock::new(),
+                first_chars: OnceLock::new(),
             })
         }
     }
 
-    pub fn serialize(&self) -> Option<Vec<u8>> {
-        self.trie.as_ref().map(|trie| trie.serialize_to_vec())
+    pub(crate) fn words(&self) -> &[WD] {
+        &self.words
+    }
+
+    /// 見出しが完全に一致する語
+    pub(crate) fn get(&self, key: &str) -> Option<&WD> {
+        let i = self.backend.as_ref()?.exact_match(key)?;
+        self.words.get(i)
+    }
+
+    /// 見出しが`prefix`で始まる語を見出し順に返す
+    pub(crate) fn predictive_search<'a>(&'a self, prefix: &str) -> impl Iterator<Item = &'a WD> {
+        let sorted = self.sorted.get_or_init(|| {
+            let mut sorted = (0..self.words.len() as u32).collect::<Vec<_>>();
+            sorted.sort_unstable_by_key(|&i| self.words[i as usize].word().key());
+            sorted
+        });
+        let start =
+            sorted.partition_point(|&i| self.words[i as usize].word().key().as_str() < prefix);
+        sorted[start..]
+            .iter()
+            .map(|&i| &self.words[i as usize])
+            .take_while(move |w| w.word().key().starts_with(prefix))
     }
 
+    /// 読みが一致する語
+    pub(crate) fn search_by_reading(&self, reading: &str) -> impl Iterator<Item = &WD> {
+        self.readings().exact(reading).map(|i| &self.words[i])
+    }
+
+    /// 読みが`prefix`で始まる語
+    pub(crate) fn predictive_search_by_reading(&self, prefix: &str) -> impl Iterator<Item = &WD> {
+        self.readings().predictive(prefix).map(|i| &self.words[i])
+    }
+
+    fn readings(&self) -> &ReadingIndex {
+        self.readings
+            .get_or_init(|| ReadingIndex::build(&self.words))
+    }
+
+    /// `c`で始まる見出しがあるか
+    #[inline]
+    pub(crate) fn has_key_starting_with(&self, c: char) -> bool {
+        self.first_chars
+            .get_or_init(|| {
+                FirstChars::from_keys(self.words.iter().map(|w| w.word().key().as_str()))
+            })
+            .contains(c)
+    }
+
+    /// 前方一致した語の番号と、一致した文字数を短い順に返す
     #[inline]
-    pub fn get<S>(&self, key: S) -> Option<&WD>
+    pub(crate) fn common_prefix_matches<S>(&self, key: S) -> impl Iterator<Item = (usize, usize)>
     where
         S: Input<Item = char>,
     {
-        if let Some(trie) = &self.trie
-            && let Some((i, _)) =
-                trie.common_prefix_search(key.iter_elements())
-                    .fold(None, |b, (i, length)| {
-                        if let Some((bi, blength)) = b
-                            && length < blength
-                        {
-                            Some((bi, blength))
-                        } else {
-                            Some((i, length))
-                        }
-                    })
-        {
-            self.words.get(i as usize)
-        } else {
-            None
-        }
+        self.backend
+            .iter()
+            .flat_map(move |backend| backend.common_prefix_search(key.clone()))
+    }
+}
+
+impl<'a, WD, B> WordLookup<'a> for DoubleArrayDictionary<WD, B>
+where
+    WD: DictionaryWordContainer + 'a,
+    B: DictionaryBackend + 'a,
+{
+    type Word = &'a WD;
+
+    #[inline]
+    fn lookup<S>(&'a self, text: S) -> Option<(Self::Word, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        let (i, chars) = self.backend.as_ref()?.longest_match(text.clone())?;
+        Some((self.words.get(i)?, text.slice_index(chars).ok()?))
+    }
+
+    #[inline]
+    fn may_start_with(&self, c: char) -> bool {
+        self.has_key_starting_with(c)
     }
 }
 
@@ -116,12 +216,99 @@ mod tests {
     #[case("炎炎の炎", get_works_case1_words(), Some(DictionaryWord::new("炎炎".into(), "えんえん".into(), "火火火火".into())))]
     #[case("水水の水", get_works_case1_words(), None)]
     #[case("水炎炎の炎", get_works_case1_words(), None)]
-    fn get_works(
+    fn lookup_works(
         #[case] key: &str,
         #[case] words: Vec<DictionaryWord>,
         #[case] expected: Option<DictionaryWord>,
     ) {
-        let dic = DoubleArrayDictionary::try_new(words).unwrap();
-        assert_that!(dic.get(key), eq(expected.as_ref()))
+        let dic = DoubleArrayDictionary::<DictionaryWord>::try_new(words).unwrap();
+        assert_that!(
+            dic.lookup(key),
+            eq(expected.as_ref().map(|w| (w, w.key().len())))
+        )
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case(FormatVersionPolicy::Rebuild, true)]
+    #[case(FormatVersionPolicy::Error, false)]
+    #[case(FormatVersionPolicy::Migrate, true)]
+    fn try_from_prepared_handles_old_version(
+        #[case] policy: FormatVersionPolicy,
+        #[case] expected_ok: bool,
+    ) -> anyhow::Result<()> {
+        let words = get_works_case1_words();
+        let mut pd = serde_cbor::from_slice::<serde_cbor::Value>(&serde_cbor::to_vec(
+            &PreparedDictionary::prepare(words.clone())?,
+        )?)?;
+        // 1.0.0の形式を再現する
+        if let serde_cbor::Value::Map(map) = &mut pd {
+            map.remove(&serde_cbor::Value::Text("checksum".into()));
+            map.remove(&serde_cbor::Value::Text("reading_index".into()));
+            if let Some(serde_cbor::Value::Array(words)) =
+                map.get_mut(&serde_cbor::Value::Text("words".into()))
+            {
+                for word in words {
+                    if let serde_cbor::Value::Map(word) = word {
+                        word.remove(&serde_cbor::Value::Text("tags".into()));
+                    }
+                }
+            }
+            map.insert(
+                serde_cbor::Value::Text("format_version".into()),
+                serde_cbor::Value::Text("1.0.0".into()),
+            );
+        }
+        let pd: PreparedDictionary<DictionaryWord> =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&pd)?)?;
+
+        let result = DoubleArrayDictionary::try_from_prepared(pd, policy);
+        if expected_ok {
+            let dic = result?;
+            assert_that!(dic.lookup("炎炎の炎"), some(eq((&words[1], "炎炎".len()))));
+        } else {
+            assert_that!(
+                result.err(),
+                some(matches_pattern!(Error::FormatVersionMismatch(
+                    eq(&"1.1.0"),
+                    eq("1.0.0")
+                )))
+            );
+        }
+        Ok(())
+    }
+
+    #[gtest]
+    fn try_from_prepared_rejects_corrupted_trie() -> anyhow::Result<()> {
+        let mut pd = PreparedDictionary::prepare(get_works_case1_words())?;
+        pd.trie_vec.truncate(pd.trie_vec.len() - 3);
+        for policy in [
+            FormatVersionPolicy::Rebuild,
+            FormatVersionPolicy::Error,
+            FormatVersionPolicy::Migrate,
+        ] {
+            assert_that!(
+                DoubleArrayDictionary::try_from_prepared(pd.clone(), policy).err(),
+                some(matches_pattern!(Error::CorruptedDictionary(anything())))
+            );
+        }
+        Ok(())
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("炎", vec!["炎", "炎炎", "炎炎炎"])]
+    #[case("炎炎", vec!["炎炎", "炎炎炎"])]
+    #[case("延", vec!["延々"])]
+    #[case("水", vec![])]
+    fn predictive_search_works(#[case] prefix: &str, #[case] expected: Vec<&str>) {
+        let dic =
+            DoubleArrayDictionary::<DictionaryWord>::try_new(get_works_case1_words()).unwrap();
+        assert_that!(
+            dic.predictive_search(prefix)
+                .map(|w| w.key().as_str())
+                .collect::<Vec<_>>(),
+            eq(&expected)
+        );
     }
 }
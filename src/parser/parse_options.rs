@@ -0,0 +1,95 @@
+use derive_getters::Getters;
+use derive_new::new;
+
+/// 作者が振ったルビを覚えて、以降のルビの無い同じ語を[`crate::GlossaryWord::Learned`]の語として補う方法。
+/// 1文字の親文字は、別の語の中の同じ漢字にも一致してしまうので覚えない
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum AutoGlossary {
+    #[default]
+    Off,
+    /// 読み進めながら覚える。最初にルビが振られるより前の出現は補わない
+    Learn,
+    /// 先に本文全体からルビを集めてから読む
+    PreScan,
+}
+
+/// 辞書に一致した語のうち、どれを注釈するか。同じ語かどうかは本文の表記で判断する
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum AnnotationPolicy {
+    /// 一致した語をすべて注釈する
+    #[default]
+    Every,
+    /// 文書中で最初に現れたものだけを注釈する
+    FirstPerDocument,
+    /// 章や節ごとに最初に現れたものだけを注釈する。
+    /// 前後の空白を除いた内容がいずれかの区切りと一致する行で、新しい章や節が始まる
+    FirstPerSection { separators: Vec<String> },
+    /// 同じ語は直近`lines`行の中で`max`回まで注釈する
+    MaxPerLines { max: usize, lines: usize },
+}
+
+/// 注釈しない語の返し方
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum SuppressedAnnotation {
+    /// [`crate::PlainPhrase`]として返す
+    #[default]
+    Plain,
+    /// [`crate::DictionaryPhrase::suppressed`]を立てて返す
+    Flag,
+}
+
+/// ルビとして扱う親文字とルビの最大文字数。超えたものは平文になる。
+/// 漢字の並びが長すぎるときは、後ろの方だけにルビを付けずに並び全体を平文にする。
+/// 閉じ括弧の無いルビや長い漢字の並びがあっても、本文の長さに比例する時間で解析を終えるための上限。
+/// オプションを取らない[`crate::ParseText::parse_iter`]などは上限を設けない
+#[derive(new, Getters, Copy, Clone, PartialEq, Debug)]
+pub struct RubyLimits {
+    max_target_chars: usize,
+    max_ruby_chars: usize,
+}
+
+impl Default for RubyLimits {
+    fn default() -> Self {
+        Self::new(64, 64)
+    }
+}
+
+impl RubyLimits {
+    /// 上限を設けない。信頼できる入力にだけ使う
+    pub fn unlimited() -> Self {
+        Self::new(usize::MAX, usize::MAX)
+    }
+}
+
+#[derive(Getters, Clone, PartialEq, Debug, Default)]
+pub struct ParseOptions {
+    auto_glossary: AutoGlossary,
+    annotation: AnnotationPolicy,
+    suppressed_annotation: SuppressedAnnotation,
+    ruby_limits: RubyLimits,
+}
+
+impl ParseOptions {
+    pub fn with_auto_glossary(mut self, auto_glossary: AutoGlossary) -> Self {
+        self.auto_glossary = auto_glossary;
+        self
+    }
+
+    pub fn with_annotation(mut self, annotation: AnnotationPolicy) -> Self {
+        self.annotation = annotation;
+        self
+    }
+
+    pub fn with_suppressed_annotation(
+        mut self,
+        suppressed_annotation: SuppressedAnnotation,
+    ) -> Self {
+        self.suppressed_annotation = suppressed_annotation;
+        self
+    }
+
+    pub fn with_ruby_limits(mut self, ruby_limits: RubyLimits) -> Self {
+        self.ruby_limits = ruby_limits;
+        self
+    }
+}
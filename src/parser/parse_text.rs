@@ -0,0 +1,126 @@
+use nom::{Compare, Input};
+
+use crate::{
+    GlossaryWord,
+    parser::{
+        IncrementalParse, ParseOptions, ParsedFragment, RubyLimits,
+        context_parser::GeneralContextParser, general_parser::WordLookup, reader::ReaderParseIter,
+    },
+};
+
+/// 外からは名指しできないので、[`ParseText`]を実装できるのはこのクレートのパーサーだけになる
+pub(crate) mod sealed {
+    use crate::parser::general_parser::GeneralParser;
+
+    pub trait HasGeneralParser {
+        type Dictionary;
+        fn general_parser(&self) -> &GeneralParser<Self::Dictionary>;
+    }
+}
+
+/// 本文を解析する。[`crate::Parser`]、[`crate::MappedParser`]、[`crate::UpdatableParser`]で共通
+pub trait ParseText: sealed::HasGeneralParser {
+    /// ルビの長さに上限を設けない
+    fn parse_iter<'a, S>(
+        &'a self,
+        text: S,
+    ) -> impl Iterator<Item = ParsedFragment<S, <Self::Dictionary as WordLookup<'a>>::Word>>
+    where
+        S: Input<Item = char> + Copy + Compare<&'static str>,
+        Self::Dictionary: WordLookup<'a>,
+    {
+        self.general_parser()
+            .parse_iter(text, GeneralContextParser::new(RubyLimits::unlimited()))
+    }
+
+    /// ルビの長さの上限は[`ParseOptions::ruby_limits`]に従う
+    fn parse_iter_with_options<'a, S>(
+        &'a self,
+        text: S,
+        options: &ParseOptions,
+    ) -> impl Iterator<
+        Item = ParsedFragment<S, GlossaryWord<S, <Self::Dictionary as WordLookup<'a>>::Word>>,
+    >
+    where
+        S: Input<Item = char> + Copy + Compare<&'static str>,
+        Self::Dictionary: WordLookup<'a>,
+    {
+        self.general_parser().parse_iter_with_options(
+            text,
+            options,
+            GeneralContextParser::new(*options.ruby_limits()),
+        )
+    }
+
+    /// 改行で区切った塊を並列に解析する。改行を含む見出しが一致しないほかは、[`Self::parse_iter`]を集めたものと同じ
+    #[cfg(feature = "parallel")]
+    fn par_parse<'a, 't>(
+        &'a self,
+        text: &'t str,
+    ) -> Vec<ParsedFragment<&'t str, <Self::Dictionary as WordLookup<'a>>::Word>>
+    where
+        Self::Dictionary: WordLookup<'a> + Sync,
+        <Self::Dictionary as WordLookup<'a>>::Word: Send,
+    {
+        crate::parser::parallel::par_parse(
+            self.general_parser(),
+            text,
+            GeneralContextParser::new(RubyLimits::unlimited()),
+        )
+    }
+
+    /// 編集された行だけを解析し直せるように、解析した結果を持っておく。改行を含む見出しは一致しない
+    fn parse_incremental<'a>(
+        &'a self,
+        text: impl Into<String>,
+    ) -> IncrementalParse<'a, <Self::Dictionary as WordLookup<'a>>::Word>
+    where
+        Self::Dictionary: WordLookup<'a>,
+    {
+        IncrementalParse::new(
+            self.general_parser(),
+            GeneralContextParser::new(RubyLimits::unlimited()),
+            text.into(),
+        )
+    }
+
+    /// 1行ずつ読みながら解析する。改行を含む見出しが一致しないほかは、全体を読んでから[`Self::parse_iter`]したものと同じ
+    fn parse_reader<'a, R>(
+        &'a self,
+        reader: R,
+    ) -> impl Iterator<
+        Item = std::io::Result<ParsedFragment<String, <Self::Dictionary as WordLookup<'a>>::Word>>,
+    >
+    where
+        R: std::io::BufRead,
+        Self::Dictionary: WordLookup<'a>,
+    {
+        ReaderParseIter::new(
+            self.general_parser(),
+            reader,
+            GeneralContextParser::new(RubyLimits::unlimited()),
+        )
+    }
+
+    /// [`Self::parse_reader`]の非同期版。[`futures::io::AsyncRead`]は[`futures::io::BufReader`]で包んで渡す。
+    /// tokioの読み手は`tokio-util`の`compat`で変換できる
+    #[cfg(feature = "async")]
+    fn parse_async_reader<'a, R>(
+        &'a self,
+        reader: R,
+    ) -> impl futures::Stream<
+        Item = std::io::Result<ParsedFragment<String, <Self::Dictionary as WordLookup<'a>>::Word>>,
+    >
+    where
+        R: futures::io::AsyncBufRead + Unpin,
+        Self::Dictionary: WordLookup<'a>,
+    {
+        crate::parser::async_reader::AsyncReaderParseStream::new(
+            self.general_parser(),
+            reader,
+            GeneralContextParser::new(RubyLimits::unlimited()),
+        )
+    }
+}
+
+impl<T> ParseText for T where T: sealed::HasGeneralParser {}
@@ -0,0 +1,152 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead},
+};
+
+use crate::parser::{
+    ParsedFragment, context_parser::ContextParser, general_parser::GeneralParser,
+    general_parser::WordLookup,
+};
+
+/// [`BufRead`]から1行ずつ読んで解析する。ルビと`\r\n`は改行をまたがないので、
+/// 改行を含む見出しの語がなければ、行ごとに解析しても全体を一度に解析したときと同じ結果になる
+pub(crate) struct ReaderParseIter<'a, R, D, CP>
+where
+    D: WordLookup<'a>,
+{
+    reader: R,
+    parser: &'a GeneralParser<D>,
+    context_parser: CP,
+    line: Vec<u8>,
+    pending: VecDeque<ParsedFragment<String, D::Word>>,
+    finished: bool,
+}
+
+impl<'a, R, D, CP> ReaderParseIter<'a, R, D, CP>
+where
+    D: WordLookup<'a>,
+{
+    pub(crate) fn new(parser: &'a GeneralParser<D>, reader: R, context_parser: CP) -> Self {
+        Self {
+            reader,
+            parser,
+            context_parser,
+            line: vec![],
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+}
+
+impl<'a, R, D, CP> Iterator for ReaderParseIter<'a, R, D, CP>
+where
+    R: BufRead,
+    D: WordLookup<'a>,
+    CP: for<'s> ContextParser<&'s str, D::Word> + Clone,
+{
+    type Item = io::Result<ParsedFragment<String, D::Word>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(fragment) = self.pending.pop_front() {
+                return Some(Ok(fragment));
+            }
+            if self.finished {
+                return None;
+            }
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => self.finished = true,
+                Ok(_) => {
+                    if let Err(e) = parse_line(
+                        self.parser,
+                        &self.line,
+                        self.context_parser.clone(),
+                        &mut self.pending,
+                    ) {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// 1行分のバイト列を解析して`pending`に積む。UTF-8として不正なら[`io::ErrorKind::InvalidData`]
+pub(crate) fn parse_line<'a, D, CP>(
+    parser: &'a GeneralParser<D>,
+    line: &[u8],
+    context_parser: CP,
+    pending: &mut VecDeque<ParsedFragment<String, D::Word>>,
+) -> io::Result<()>
+where
+    D: WordLookup<'a>,
+    CP: for<'s> ContextParser<&'s str, D::Word>,
+{
+    let line =
+        std::str::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    pending.extend(
+        parser
+            .parse_iter(line, context_parser)
+            .map(|f| f.map_text(str::to_string)),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::{
+        ParseText,
+        parser::test_fixture::{BROKEN_RUBY, KOKORO, SAMPLE, parser},
+    };
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    #[gtest]
+    #[rstest]
+    #[case(SAMPLE)]
+    #[case(BROKEN_RUBY)]
+    #[case(KOKORO)]
+    fn parse_reader_matches_parse_iter(
+        #[case] text: &str,
+        #[values(1, 2, 3, 5, 8192)] capacity: usize,
+    ) -> anyhow::Result<()> {
+        let parser = parser();
+        let expected = parser
+            .parse_iter(text)
+            .map(|f| f.map_text(str::to_string))
+            .collect::<Vec<_>>();
+        let actual = parser
+            .parse_reader(BufReader::with_capacity(capacity, text.as_bytes()))
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_that!(actual, eq(&expected));
+        Ok(())
+    }
+
+    #[gtest]
+    fn parse_reader_rejects_invalid_utf8() {
+        let parser = parser();
+        let results = parser
+            .parse_reader(&b"\xe8\x8c\xb6\xe5\xb1\x8b\n\xe8\x8c"[..])
+            .map(|r| r.map_err(|e| e.kind()))
+            .collect::<Vec<_>>();
+        assert_that!(
+            results,
+            elements_are![
+                ok(anything()),
+                ok(anything()),
+                err(eq(&io::ErrorKind::InvalidData))
+            ]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    fmt::{self, Debug, Display},
+    ops::Range,
+    str::CharIndices,
+};
+
+use nom::{Compare, CompareResult, Input, Needed, Offset};
+use ropey::RopeSlice;
+
+use crate::parser::input::{compare_chars, slice_index};
+
+/// ropeyのロープを写さずに解析の入力にする。語句の長さや位置はUTF-8のバイトで数え、
+/// 語句がロープのどこにあるかは[`RopeInput::byte_range`]や[`RopeInput::char_range`]で分かる
+#[derive(Clone, Copy)]
+pub struct RopeInput<'a> {
+    rope: RopeSlice<'a>,
+    /// `rope`での範囲(バイト)
+    start: usize,
+    end: usize,
+    /// `start`から始まる、いまの塊の中の文字列。解析はほとんど塊の中で済むので、木をたどらずに読める
+    head: &'a str,
+}
+
+impl<'a> RopeInput<'a> {
+    pub fn new(rope: RopeSlice<'a>) -> Self {
+        Self::at(rope, 0, rope.len_bytes())
+    }
+
+    fn at(rope: RopeSlice<'a>, start: usize, end: usize) -> Self {
+        let head = if start < end {
+            let (chunk, chunk_start, _, _) = rope.chunk_at_byte(start);
+            let head = &chunk[start - chunk_start..];
+            &head[..head.len().min(end - start)]
+        } else {
+            ""
+        };
+        Self {
+            rope,
+            start,
+            end,
+            head,
+        }
+    }
+
+    pub fn as_slice(&self) -> RopeSlice<'a> {
+        self.rope.byte_slice(self.start..self.end)
+    }
+
+    /// [`RopeInput::new`]に渡したロープでの範囲(バイト)
+    pub fn byte_range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// [`RopeInput::new`]に渡したロープでの範囲(文字数)
+    pub fn char_range(&self) -> Range<usize> {
+        self.rope.byte_to_char(self.start)..self.rope.byte_to_char(self.end)
+    }
+}
+
+impl<'a> From<RopeSlice<'a>> for RopeInput<'a> {
+    fn from(value: RopeSlice<'a>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<'a> From<&'a ropey::Rope> for RopeInput<'a> {
+    fn from(value: &'a ropey::Rope) -> Self {
+        Self::new(value.slice(..))
+    }
+}
+
+/// `&str`と同じく、中身が同じなら等しい
+impl PartialEq for RopeInput<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.input_len() == other.input_len() && self.iter_elements().eq(other.iter_elements())
+    }
+}
+
+impl Display for RopeInput<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.iter_elements().try_for_each(|c| write!(f, "{c}"))
+    }
+}
+
+impl Debug for RopeInput<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RopeInput")
+            .field("byte_range", &self.byte_range())
+            .field("text", &self.to_string())
+            .finish()
+    }
+}
+
+/// [`RopeInput`]の文字と、その位置(バイト)。塊を使い切ったら次の塊を引く
+#[derive(Clone)]
+pub struct RopeCharIndices<'a> {
+    rope: RopeSlice<'a>,
+    /// `chunk`の始まりの`RopeInput`での位置
+    offset: usize,
+    /// `rope`での`RopeInput`の範囲
+    start: usize,
+    end: usize,
+    chunk: CharIndices<'a>,
+}
+
+impl Iterator for RopeCharIndices<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((i, c)) = self.chunk.next() {
+                return Some((self.offset + i, c));
+            }
+            self.offset += self.chunk.offset();
+            let pos = self.start + self.offset;
+            if pos >= self.end {
+                return None;
+            }
+            self.chunk = RopeInput::at(self.rope, pos, self.end).head.char_indices();
+        }
+    }
+}
+
+impl<'a> Input for RopeInput<'a> {
+    type Item = char;
+    type Iter = std::iter::Map<RopeCharIndices<'a>, fn((usize, char)) -> char>;
+    type IterIndices = RopeCharIndices<'a>;
+
+    #[inline]
+    fn input_len(&self) -> usize {
+        self.end - self.start
+    }
+
+    #[inline]
+    fn take(&self, index: usize) -> Self {
+        Self {
+            end: self.start + index,
+            head: &self.head[..self.head.len().min(index)],
+            ..*self
+        }
+    }
+
+    #[inline]
+    fn take_from(&self, index: usize) -> Self {
+        if index < self.head.len() {
+            Self {
+                start: self.start + index,
+                head: &self.head[index..],
+                ..*self
+            }
+        } else {
+            Self::at(self.rope, self.start + index, self.end)
+        }
+    }
+
+    #[inline]
+    fn take_split(&self, index: usize) -> (Self, Self) {
+        (self.take_from(index), self.take(index))
+    }
+
+    #[inline]
+    fn position<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(Self::Item) -> bool,
+    {
+        self.iter_indices()
+            .find(|&(_, c)| predicate(c))
+            .map(|(i, _)| i)
+    }
+
+    #[inline]
+    fn iter_elements(&self) -> Self::Iter {
+        self.iter_indices().map(|(_, c)| c)
+    }
+
+    #[inline]
+    fn iter_indices(&self) -> Self::IterIndices {
+        RopeCharIndices {
+            rope: self.rope,
+            offset: 0,
+            start: self.start,
+            end: self.end,
+            chunk: self.head.char_indices(),
+        }
+    }
+
+    #[inline]
+    fn slice_index(&self, count: usize) -> Result<usize, Needed> {
+        slice_index(self.iter_indices(), self.input_len(), count)
+    }
+}
+
+impl Compare<&str> for RopeInput<'_> {
+    fn compare(&self, t: &str) -> CompareResult {
+        if self.head.len() >= t.len() {
+            if self.head.as_bytes().starts_with(t.as_bytes()) {
+                CompareResult::Ok
+            } else {
+                CompareResult::Error
+            }
+        } else {
+            compare_chars(self.iter_elements(), t, |a, b| a == b)
+        }
+    }
+
+    fn compare_no_case(&self, t: &str) -> CompareResult {
+        compare_chars(self.iter_elements(), t, |a, b| {
+            a.to_lowercase().eq(b.to_lowercase())
+        })
+    }
+}
+
+impl Offset for RopeInput<'_> {
+    fn offset(&self, second: &Self) -> usize {
+        second.start - self.start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::{DictionaryWord, ParseText, ParsedFragment, Parser};
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn parser() -> Parser {
+        Parser::try_new_with_dic(vec![
+            DictionaryWord::new("若々しい".into(), "わかわか".into(), "".into()),
+            DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into()),
+        ])
+        .unwrap()
+    }
+
+    /// 語句の本文とバイトでの範囲
+    fn summary<S: Display, DW>(
+        fragments: impl Iterator<Item = ParsedFragment<S, DW>>,
+        range: impl Fn(&S) -> Range<usize>,
+    ) -> Vec<(String, Range<usize>)> {
+        fragments
+            .map(|f| (f.fragment().to_string(), range(f.fragment())))
+            .collect()
+    }
+
+    fn str_summary(parser: &Parser, text: &str) -> Vec<(String, Range<usize>)> {
+        summary(parser.parse_iter(text), |f| {
+            let start = f.as_ptr() as usize - text.as_ptr() as usize;
+            start..start + f.len()
+        })
+    }
+
+    const SAMPLE: &str = "若々しい茶屋の娘\r\n|玄人《くろうと》と\n\n茶屋　茶屋";
+
+    #[gtest]
+    #[rstest]
+    #[case(SAMPLE)]
+    #[case(include_str!("../../benches/test_data/kokoro_utf8.txt"))]
+    fn rope_matches_str(#[case] text: &str) {
+        let parser = parser();
+        let rope = Rope::from_str(text);
+        assert_that!(
+            summary(
+                parser.parse_iter(RopeInput::from(&rope)),
+                RopeInput::byte_range
+            ),
+            eq(&str_summary(&parser, text))
+        );
+    }
+
+    #[gtest]
+    fn fragments_across_chunk_boundaries() {
+        let parser = parser();
+        let expected = str_summary(&parser, SAMPLE);
+        let mut crossed = 0;
+        for padding in (0..2048).step_by(7) {
+            let pad = "x".repeat(padding);
+            let rope = Rope::from_str(&format!("{pad}{SAMPLE}{pad}"));
+            let slice = rope.slice(padding..padding + SAMPLE.chars().count());
+            if slice.chunks().count() > 1 {
+                crossed += 1;
+            }
+            assert_that!(
+                summary(
+                    parser.parse_iter(RopeInput::new(slice)),
+                    RopeInput::byte_range
+                ),
+                eq(&expected),
+                "padding: {padding}"
+            );
+        }
+        assert_that!(crossed, gt(0));
+    }
+
+    #[gtest]
+    fn char_range_counts_from_slice_start() {
+        let parser = parser();
+        let rope = Rope::from_str("前置き𠮷の茶屋");
+        let ranges = parser
+            .parse_iter(RopeInput::new(rope.slice(3..)))
+            .map(|f| (f.fragment().byte_range(), f.fragment().char_range()))
+            .collect::<Vec<_>>();
+        assert_that!(ranges, elements_are![eq(&(0..7, 0..2)), eq(&(7..13, 2..4))]);
+    }
+}
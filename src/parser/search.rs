@@ -0,0 +1,46 @@
+use crate::DictionaryWord;
+
+/// 辞書検索の結果の並べ方
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum SearchOrder {
+    /// 見出しの短い順。同じ長さなら見出し順
+    #[default]
+    KeyLength,
+    /// [`WordFrequency::frequency`]の大きい順。同じなら見出しの短い順
+    Frequency,
+}
+
+/// 辞書の語の付加情報から、検索結果を並べるための頻度を得る
+pub trait WordFrequency {
+    fn frequency(&self) -> i64 {
+        0
+    }
+}
+
+impl WordFrequency for () {}
+
+#[cfg(feature = "yomitan")]
+impl WordFrequency for crate::YomitanTermMeta {
+    fn frequency(&self) -> i64 {
+        *self.score()
+    }
+}
+
+pub(crate) fn sort_words<X>(words: &mut [&DictionaryWord<X>], order: SearchOrder)
+where
+    X: WordFrequency,
+{
+    let key_length = |w: &DictionaryWord<X>| w.key().chars().count();
+    match order {
+        SearchOrder::KeyLength => {
+            words.sort_by(|a, b| key_length(a).cmp(&key_length(b)).then(a.key().cmp(b.key())))
+        }
+        SearchOrder::Frequency => words.sort_by(|a, b| {
+            b.extra()
+                .frequency()
+                .cmp(&a.extra().frequency())
+                .then(key_length(a).cmp(&key_length(b)))
+                .then(a.key().cmp(b.key()))
+        }),
+    }
+}
@@ -0,0 +1,23 @@
+//! 読み込み方や区切り方を変えても[`crate::ParseText::parse_iter`]と同じ結果になるかを確かめるテストで共有するもの
+
+use crate::{DictionaryWord, Parser};
+
+/// 空白を含む見出しと、その一部だけの見出しを持つ辞書
+pub(crate) fn parser() -> Parser {
+    Parser::try_new_with_dic(vec![
+        DictionaryWord::new("若々しい".into(), "わかわか".into(), "".into()),
+        DictionaryWord::new("茶屋".into(), "ちゃや".into(), "".into()),
+        DictionaryWord::new("茶屋 の".into(), "".into(), "".into()),
+    ])
+    .unwrap()
+}
+
+/// 辞書の語、2種類のルビ、CRLFと空行を含む本文
+pub(crate) const SAMPLE: &str =
+    "若々しい茶屋 の娘|玄人《くろうと》\r\n玄人（くろうと）\r\n\r\n茶屋";
+
+/// 行をまたいで閉じ括弧の無いルビを含む本文
+pub(crate) const BROKEN_RUBY: &str = "茶屋\n\n|玄人《くろ\nうと》";
+
+/// 長い本文
+pub(crate) const KOKORO: &str = include_str!("../../benches/test_data/kokoro_utf8.txt");
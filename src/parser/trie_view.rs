@@ -0,0 +1,278 @@
+//! crawdadの`Trie::serialize_to_vec`が書き出したバイト列を、複製せずにそのまま引く。
+//! 不正なバイト列に対してはpanicせず、一致なしとして扱う。
+
+const OFFSET_MASK: u32 = 0x7fff_ffff;
+const INVALID_CODE: u32 = u32::MAX;
+const END_CODE: u32 = 0;
+const NODE_BYTES: usize = 8;
+
+#[derive(Clone, Copy)]
+pub(crate) struct TrieView<'a> {
+    table: &'a [u8],
+    alphabet_size: u32,
+    nodes: &'a [u8],
+}
+
+impl<'a> TrieView<'a> {
+    /// 先頭からトライを読み取り、残りのバイト列と共に返す。長さが足りなければ`None`
+    pub(crate) fn parse(source: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (table_len, source) = split_u32(source)?;
+        let (table, source) = source.split_at_checked((table_len as usize).checked_mul(4)?)?;
+        let (alphabet_size, source) = split_u32(source)?;
+        let (nodes_len, source) = split_u32(source)?;
+        let (nodes, source) =
+            source.split_at_checked((nodes_len as usize).checked_mul(NODE_BYTES)?)?;
+        if nodes.is_empty() {
+            return None;
+        }
+        Some((
+            Self {
+                table,
+                alphabet_size,
+                nodes,
+            },
+            source,
+        ))
+    }
+
+    /// crawdadの`Trie`に渡しても範囲外を読まないか。crawdadは子の位置`base ^ code`を確かめずに
+    /// 読むので、表の文字コードが字母の数より小さく、使われている全てのノードで`base`の属する
+    /// ブロック(字母の数を2の冪に切り上げた長さ)がノードの数に収まっていることを確かめる
+    pub(crate) fn is_well_formed(&self) -> bool {
+        let node_count = self.nodes.len() / NODE_BYTES;
+        let Some(block_mask) = self
+            .alphabet_size
+            .checked_next_power_of_two()
+            .map(|len| len.max(2) - 1)
+        else {
+            return false;
+        };
+        let codes_in_alphabet = (0..self.table.len() / 4).all(|i| {
+            read_u32(self.table, i)
+                .is_some_and(|code| code == INVALID_CODE || code < self.alphabet_size)
+        });
+        codes_in_alphabet
+            && (0..node_count as u32).all(|node_idx| {
+                let Some((base, check)) = self.node(node_idx) else {
+                    return false;
+                };
+                // 空きノードと根は、子をたどる途中で親として確かめられることがない
+                let vacant = base == OFFSET_MASK && check == OFFSET_MASK;
+                vacant
+                    || (node_idx == 0 || ((check & OFFSET_MASK) as usize) < node_count)
+                        && (is_leaf(base)
+                            || (((base & OFFSET_MASK) | block_mask) as usize) < node_count)
+            })
+    }
+
+    #[inline(always)]
+    pub(crate) fn exact_match<I>(&self, key: I) -> Option<u32>
+    where
+        I: IntoIterator<Item = char>,
+    {
+        let mut node_idx = 0;
+        for c in key {
+            node_idx = self.child_idx(node_idx, self.code(c)?)?;
+        }
+        self.value_of(node_idx)
+    }
+
+    /// `c`で始まる見出しがありうるか
+    #[inline(always)]
+    pub(crate) fn has_child_of_root(&self, c: char) -> bool {
+        self.code(c)
+            .and_then(|code| self.child_idx(0, code))
+            .is_some()
+    }
+
+    #[inline(always)]
+    pub(crate) fn common_prefix_search<I>(&self, haystack: I) -> CommonPrefixSearchView<'a, I>
+    where
+        I: Iterator<Item = char>,
+    {
+        CommonPrefixSearchView {
+            haystack,
+            haystack_pos: 0,
+            trie: *self,
+            node_idx: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn code(&self, c: char) -> Option<u32> {
+        read_u32(self.table, c as usize).filter(|&code| code != INVALID_CODE)
+    }
+
+    #[inline(always)]
+    fn node(&self, node_idx: u32) -> Option<(u32, u32)> {
+        let i = node_idx as usize * 2;
+        Some((read_u32(self.nodes, i)?, read_u32(self.nodes, i + 1)?))
+    }
+
+    #[inline(always)]
+    fn child_idx(&self, node_idx: u32, code: u32) -> Option<u32> {
+        let (base, _) = self.node(node_idx)?;
+        if is_leaf(base) {
+            return None;
+        }
+        let child_idx = (base & OFFSET_MASK) ^ code;
+        let (_, check) = self.node(child_idx)?;
+        (check & OFFSET_MASK == node_idx).then_some(child_idx)
+    }
+
+    /// ノードに結び付いた値。葉自身か、終端の葉を子に持つ場合だけ値がある
+    #[inline(always)]
+    fn value_of(&self, node_idx: u32) -> Option<u32> {
+        let (base, check) = self.node(node_idx)?;
+        if is_leaf(base) {
+            Some(base & OFFSET_MASK)
+        } else if check & !OFFSET_MASK != 0 {
+            let leaf_idx = (base & OFFSET_MASK) ^ END_CODE;
+            let (leaf_base, _) = self.node(leaf_idx)?;
+            Some(leaf_base & OFFSET_MASK)
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) struct CommonPrefixSearchView<'a, I> {
+    haystack: I,
+    haystack_pos: usize,
+    trie: TrieView<'a>,
+    node_idx: u32,
+}
+
+impl<I> Iterator for CommonPrefixSearchView<'_, I>
+where
+    I: Iterator<Item = char>,
+{
+    type Item = (u32, usize);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        for c in self.haystack.by_ref() {
+            let code = self.trie.code(c)?;
+            self.node_idx = self.trie.child_idx(self.node_idx, code)?;
+            self.haystack_pos += 1;
+            if let Some(value) = self.trie.value_of(self.node_idx) {
+                return Some((value, self.haystack_pos));
+            }
+        }
+        None
+    }
+}
+
+#[inline(always)]
+const fn is_leaf(base: u32) -> bool {
+    base & !OFFSET_MASK != 0
+}
+
+#[inline(always)]
+fn read_u32(bytes: &[u8], index: usize) -> Option<u32> {
+    let start = index.checked_mul(4)?;
+    bytes
+        .get(start..start + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn split_u32(source: &[u8]) -> Option<(u32, &[u8])> {
+    let (head, rest) = source.split_at_checked(4)?;
+    Some((
+        u32::from_le_bytes([head[0], head[1], head[2], head[3]]),
+        rest,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crawdad::Trie;
+
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn keys() -> Vec<&'static str> {
+        vec!["世界", "世界中", "世論調査", "統計調査", "炎", "炎炎"]
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("世界", Some(0))]
+    #[case("世界中", Some(1))]
+    #[case("炎炎", Some(5))]
+    #[case("世", None)]
+    #[case("世界中で", None)]
+    #[case("日本", None)]
+    fn exact_match_works(#[case] key: &str, #[case] expected: Option<u32>) {
+        let bytes = Trie::from_keys(keys()).unwrap().serialize_to_vec();
+        let (trie, rest) = TrieView::parse(&bytes).unwrap();
+        assert_that!(rest, is_empty());
+        assert_that!(trie.exact_match(key.chars()), eq(expected));
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("世界中の統計")]
+    #[case("炎炎の炎")]
+    #[case("世論調査")]
+    #[case("水")]
+    fn common_prefix_search_matches_crawdad(#[case] haystack: &str) {
+        let original = Trie::from_keys(keys()).unwrap();
+        let bytes = original.serialize_to_vec();
+        let (trie, _) = TrieView::parse(&bytes).unwrap();
+        assert_that!(
+            trie.common_prefix_search(haystack.chars())
+                .collect::<Vec<_>>(),
+            eq(&original
+                .common_prefix_search(haystack.chars())
+                .collect::<Vec<_>>())
+        );
+    }
+
+    #[gtest]
+    fn built_tries_are_well_formed() {
+        let many = ('\u{4E00}'..'\u{4FFF}')
+            .flat_map(|a| ['の', 'に', a].map(|b| format!("{a}{b}")))
+            .collect::<Vec<_>>();
+        for bytes in [
+            Trie::from_keys(keys()).unwrap().serialize_to_vec(),
+            Trie::from_keys(&many).unwrap().serialize_to_vec(),
+        ] {
+            let (trie, _) = TrieView::parse(&bytes).unwrap();
+            assert_that!(trie.is_well_formed(), eq(true));
+        }
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case::code(|bytes: &mut Vec<u8>| bytes[4 + 'A' as usize * 4..][..4].fill(0x7F))]
+    #[case::base(|bytes: &mut Vec<u8>| {
+        let table_len = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        // 根のbase
+        bytes[4 + table_len * 4 + 8..][..4].copy_from_slice(&0x7000_0000u32.to_le_bytes());
+    })]
+    fn out_of_bounds_trie_is_not_well_formed(#[case] corrupt: fn(&mut Vec<u8>)) {
+        let mut bytes = Trie::from_keys(keys()).unwrap().serialize_to_vec();
+        corrupt(&mut bytes);
+        let (trie, _) = TrieView::parse(&bytes).unwrap();
+        assert_that!(trie.is_well_formed(), eq(false));
+    }
+
+    #[gtest]
+    fn truncated_bytes_are_rejected_or_never_panic() {
+        let bytes = Trie::from_keys(keys()).unwrap().serialize_to_vec();
+        for len in 0..bytes.len() {
+            if let Some((trie, _)) = TrieView::parse(&bytes[..len]) {
+                let _ = trie.common_prefix_search("世界中".chars()).count();
+            }
+        }
+        let mut broken = bytes.clone();
+        for b in broken.iter_mut().skip(8) {
+            *b = 0xFF;
+        }
+        if let Some((trie, _)) = TrieView::parse(&broken) {
+            assert_that!(trie.exact_match("世界".chars()), none());
+        }
+    }
+}
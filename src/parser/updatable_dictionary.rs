@@ -0,0 +1,355 @@
+use std::{collections::HashMap, thread::JoinHandle};
+
+use nom::Input;
+
+use crate::{
+    DictionaryWord,
+    parser::{
+        Error, Result, WordLookup, first_chars::FirstChars, parse_dictionary::DoubleArrayDictionary,
+    },
+};
+
+/// 語を追加・削除できる辞書。変更は小さな上書き領域に溜め、
+/// ある程度溜まったら別スレッドでダブル配列に畳み込む。畳み込みの間も古いダブル配列と上書き領域で引ける
+pub struct UpdatableDictionary<X = ()> {
+    base: DoubleArrayDictionary<DictionaryWord<X>>,
+    /// 見出しごとの変更。`None`は削除を表す。数値は変更の世代
+    overlay: HashMap<String, (u64, Option<DictionaryWord<X>>)>,
+    overlay_max_chars: usize,
+    /// 上書き領域の見出しの先頭の文字。削除した見出しの分も含む
+    overlay_first_chars: FirstChars,
+    generation: u64,
+    compaction_threshold: usize,
+    compaction: Option<Compaction<X>>,
+}
+
+struct Compaction<X> {
+    /// この世代までの変更を畳み込んでいる
+    generation: u64,
+    handle: JoinHandle<Result<DoubleArrayDictionary<DictionaryWord<X>>>>,
+}
+
+impl<X> From<DoubleArrayDictionary<DictionaryWord<X>>> for UpdatableDictionary<X> {
+    fn from(value: DoubleArrayDictionary<DictionaryWord<X>>) -> Self {
+        Self {
+            base: value,
+            overlay: HashMap::new(),
+            overlay_max_chars: 0,
+            overlay_first_chars: FirstChars::default(),
+            generation: 0,
+            compaction_threshold: Self::DEFAULT_COMPACTION_THRESHOLD,
+            compaction: None,
+        }
+    }
+}
+
+impl<X> UpdatableDictionary<X> {
+    pub(crate) const DEFAULT_COMPACTION_THRESHOLD: usize = 256;
+
+    pub(crate) fn set_compaction_threshold(&mut self, threshold: usize) {
+        self.compaction_threshold = threshold;
+    }
+
+    pub(crate) fn is_compacting(&self) -> bool {
+        self.compaction.is_some()
+    }
+
+    pub(crate) fn overlay_len(&self) -> usize {
+        self.overlay.len()
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&DictionaryWord<X>> {
+        match self.overlay.get(key) {
+            Some((_, word)) => word.as_ref(),
+            None => self.base.get(key),
+        }
+    }
+
+    /// 上書きされていない元の語のうち、最長一致したものの番号と文字数
+    #[inline]
+    fn longest_base_match<S>(&self, text: S) -> Option<(usize, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        self.base
+            .common_prefix_matches(text)
+            .filter(|(i, _)| {
+                self.overlay.is_empty() || !self.overlay.contains_key(self.base.words()[*i].key())
+            })
+            .last()
+    }
+
+    /// 上書き領域で最長一致した語と文字数
+    #[inline]
+    fn longest_overlay_match<S>(&self, text: S) -> Option<(&DictionaryWord<X>, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        if self.overlay.is_empty() {
+            return None;
+        }
+        let mut key = String::new();
+        let mut best = None;
+        for (chars, c) in text
+            .iter_elements()
+            .take(self.overlay_max_chars)
+            .enumerate()
+        {
+            key.push(c);
+            if let Some((_, Some(word))) = self.overlay.get(&key) {
+                best = Some((word, chars + 1));
+            }
+        }
+        best
+    }
+}
+
+impl<X> UpdatableDictionary<X>
+where
+    X: Clone + Send + 'static,
+{
+    /// 見出しが既にあれば何もせず`false`を返す
+    pub(crate) fn insert(&mut self, word: DictionaryWord<X>) -> Result<bool> {
+        if self.get(word.key()).is_some() {
+            return Ok(false);
+        }
+        self.set(word)?;
+        Ok(true)
+    }
+
+    /// 見出しが同じ語を置き換え、元の語を返す。無ければ追加する
+    pub(crate) fn replace(&mut self, word: DictionaryWord<X>) -> Result<Option<DictionaryWord<X>>> {
+        let old = self.get(word.key()).cloned();
+        self.set(word)?;
+        Ok(old)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Result<Option<DictionaryWord<X>>> {
+        let old = self.get(key).cloned();
+        if old.is_some() {
+            self.write_overlay(key.into(), None)?;
+        }
+        Ok(old)
+    }
+
+    fn set(&mut self, word: DictionaryWord<X>) -> Result<()> {
+        if word.key().is_empty() {
+            return Err(Error::EmptyDictionaryKey);
+        }
+        self.write_overlay(word.key().clone(), Some(word))
+    }
+
+    fn write_overlay(&mut self, key: String, word: Option<DictionaryWord<X>>) -> Result<()> {
+        self.generation += 1;
+        self.overlay_max_chars = self.overlay_max_chars.max(key.chars().count());
+        self.overlay_first_chars.extend(key.chars().next());
+        self.overlay.insert(key, (self.generation, word));
+        self.poll_compaction()?;
+        if self.compaction.is_none() && self.overlay.len() >= self.compaction_threshold {
+            self.start_compaction();
+        }
+        Ok(())
+    }
+
+    /// 現在の語をすべて集め、別スレッドでダブル配列を作り始める
+    fn start_compaction(&mut self) {
+        let words = self
+            .base
+            .words()
+            .iter()
+            .filter(|w| !self.overlay.contains_key(w.key()))
+            .chain(self.overlay.values().filter_map(|(_, w)| w.as_ref()))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.compaction = Some(Compaction {
+            generation: self.generation,
+            handle: std::thread::spawn(move || DoubleArrayDictionary::try_new(words)),
+        });
+    }
+
+    /// 畳み込みが終わっていれば結果を取り込み、`true`を返す
+    pub(crate) fn poll_compaction(&mut self) -> Result<bool> {
+        if self
+            .compaction
+            .as_ref()
+            .is_some_and(|c| c.handle.is_finished())
+        {
+            self.finish_compaction()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// 上書き領域をすべてダブル配列に畳み込むまで待つ
+    pub(crate) fn compact(&mut self) -> Result<()> {
+        self.finish_compaction()?;
+        if !self.overlay.is_empty() {
+            self.start_compaction();
+            self.finish_compaction()?;
+        }
+        Ok(())
+    }
+
+    fn finish_compaction(&mut self) -> Result<()> {
+        let Some(compaction) = self.compaction.take() else {
+            return Ok(());
+        };
+        let base = compaction
+            .handle
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))?;
+        self.base = base;
+        // 畳み込み中に変更された見出しは上書き領域に残す
+        self.overlay
+            .retain(|_, (generation, _)| *generation > compaction.generation);
+        self.overlay_max_chars = self
+            .overlay
+            .keys()
+            .map(|k| k.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.overlay_first_chars = FirstChars::from_keys(self.overlay.keys().map(String::as_str));
+        Ok(())
+    }
+}
+
+impl<'a, X> WordLookup<'a> for UpdatableDictionary<X>
+where
+    X: 'a,
+{
+    type Word = &'a DictionaryWord<X>;
+
+    #[inline]
+    fn lookup<S>(&'a self, text: S) -> Option<(Self::Word, usize)>
+    where
+        S: Input<Item = char>,
+    {
+        let base = self
+            .longest_base_match(text.clone())
+            .map(|(i, chars)| (&self.base.words()[i], chars));
+        let overlay = self.longest_overlay_match(text.clone());
+        let (word, chars) = match (base, overlay) {
+            (Some(b), Some(o)) if b.1 > o.1 => b,
+            (_, Some(o)) => o,
+            (b, None) => b?,
+        };
+        Some((word, text.slice_index(chars).ok()?))
+    }
+
+    #[inline]
+    fn may_start_with(&self, c: char) -> bool {
+        self.base.has_key_starting_with(c) || self.overlay_first_chars.contains(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use rstest::*;
+
+    fn dictionary() -> UpdatableDictionary {
+        UpdatableDictionary::from(
+            DoubleArrayDictionary::try_new(vec![
+                DictionaryWord::new("魔導".into(), "まどう".into(), "元".into()),
+                DictionaryWord::new("魔導書".into(), "まどうしょ".into(), "元".into()),
+                DictionaryWord::new("剣".into(), "けん".into(), "元".into()),
+            ])
+            .unwrap(),
+        )
+    }
+
+    fn lookup(dic: &UpdatableDictionary, text: &str) -> Option<(String, String)> {
+        dic.lookup(text)
+            .map(|(w, len)| (text[..len].to_string(), w.description().clone()))
+    }
+
+    #[gtest]
+    #[rstest]
+    #[case("魔導書を読む", Some(("魔導", "新")))]
+    #[case("剣を抜く", None)]
+    #[case("聖剣を抜く", Some(("聖剣", "新")))]
+    #[case("杖", None)]
+    fn lookup_with_overlay_works(#[case] text: &str, #[case] expected: Option<(&str, &str)>) {
+        let mut dic = dictionary();
+        dic.replace(DictionaryWord::new("魔導".into(), "".into(), "新".into()))
+            .unwrap();
+        dic.remove("魔導書").unwrap();
+        dic.remove("剣").unwrap();
+        dic.insert(DictionaryWord::new("聖剣".into(), "".into(), "新".into()))
+            .unwrap();
+        assert_that!(
+            lookup(&dic, text),
+            eq(&expected.map(|(k, d)| (k.to_string(), d.to_string())))
+        );
+    }
+
+    #[gtest]
+    fn insert_remove_replace_works() -> anyhow::Result<()> {
+        let mut dic = dictionary();
+        assert_that!(
+            dic.insert(DictionaryWord::new("剣".into(), "".into(), "新".into()))?,
+            eq(false)
+        );
+        assert_that!(
+            dic.insert(DictionaryWord::new("杖".into(), "".into(), "新".into()))?,
+            eq(true)
+        );
+        assert_that!(
+            dic.replace(DictionaryWord::new("剣".into(), "".into(), "新".into()))?
+                .map(|w| w.description().clone()),
+            some(eq("元"))
+        );
+        assert_that!(
+            dic.remove("杖")?.map(|w| w.description().clone()),
+            some(eq("新"))
+        );
+        assert_that!(dic.remove("杖")?, none());
+        assert_that!(dic.get("杖"), none());
+        assert_that!(
+            dic.insert(DictionaryWord::new("".into(), "".into(), "".into())),
+            err(matches_pattern!(Error::EmptyDictionaryKey))
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn compact_works() -> anyhow::Result<()> {
+        let mut dic = dictionary();
+        dic.replace(DictionaryWord::new("魔導".into(), "".into(), "新".into()))?;
+        dic.remove("剣")?;
+        dic.insert(DictionaryWord::new("聖剣".into(), "".into(), "新".into()))?;
+        dic.compact()?;
+        assert_that!(dic.overlay_len(), eq(0));
+        assert_that!(dic.base.words().len(), eq(3));
+        assert_that!(
+            lookup(&dic, "魔導"),
+            some(eq(&("魔導".into(), "新".into())))
+        );
+        assert_that!(
+            lookup(&dic, "聖剣"),
+            some(eq(&("聖剣".into(), "新".into())))
+        );
+        assert_that!(lookup(&dic, "剣"), none());
+        Ok(())
+    }
+
+    #[gtest]
+    fn background_compaction_keeps_newer_changes() -> anyhow::Result<()> {
+        let mut dic = dictionary();
+        dic.set_compaction_threshold(2);
+        dic.insert(DictionaryWord::new("杖".into(), "".into(), "1".into()))?;
+        dic.insert(DictionaryWord::new("盾".into(), "".into(), "1".into()))?;
+        assert_that!(dic.is_compacting(), eq(true));
+        // 畳み込み中も引ける
+        assert_that!(lookup(&dic, "盾"), some(eq(&("盾".into(), "1".into()))));
+        dic.replace(DictionaryWord::new("杖".into(), "".into(), "2".into()))?;
+        dic.finish_compaction()?;
+        assert_that!(dic.is_compacting(), eq(false));
+        assert_that!(dic.overlay_len(), eq(1));
+        assert_that!(lookup(&dic, "杖"), some(eq(&("杖".into(), "2".into()))));
+        assert_that!(lookup(&dic, "盾"), some(eq(&("盾".into(), "1".into()))));
+        Ok(())
+    }
+}
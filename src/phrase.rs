@@ -17,6 +17,35 @@ pub enum Phrase<S = String, DW = DictionaryWord> {
 
 pub type PhraseRef<'a, S = str, DW = DictionaryWord> = Phrase<&'a S, &'a DW>;
 
+impl<S, DW> Phrase<S, DW> {
+    /// 本文を指す部分を`f`で変換する。借用した本文から所有する文字列に移すときなどに使う
+    pub fn map_text<T>(self, f: impl Fn(S) -> T) -> Phrase<T, DW> {
+        match self {
+            Self::Ruby(p) => Phrase::Ruby(RubyPhrase::new(f(p.target), f(p.ruby), p.ruby_type)),
+            Self::DictionaryWord(dw) => Phrase::DictionaryWord(DictionaryPhrase {
+                target: f(dw.target),
+                word: dw.word,
+                layer: dw.layer,
+                suppressed: dw.suppressed,
+            }),
+            Self::NewLine(nl) => Phrase::NewLine(nl),
+            Self::WhiteSpace(ws) => Phrase::WhiteSpace(ws),
+            Self::Plain(pl) => Phrase::Plain(PlainPhrase::new(f(pl.target))),
+        }
+    }
+
+    /// 辞書の語を`f`で変換する
+    pub fn map_word<T>(self, f: impl FnOnce(DW) -> T) -> Phrase<S, T> {
+        match self {
+            Self::Ruby(p) => Phrase::Ruby(p),
+            Self::DictionaryWord(dw) => Phrase::DictionaryWord(dw.map_word(f)),
+            Self::NewLine(nl) => Phrase::NewLine(nl),
+            Self::WhiteSpace(ws) => Phrase::WhiteSpace(ws),
+            Self::Plain(pl) => Phrase::Plain(pl),
+        }
+    }
+}
+
 impl<S: Display, DW> Display for Phrase<S, DW> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -66,6 +95,46 @@ impl<S: Display> Display for RubyPhrase<S> {
 pub struct DictionaryPhrase<S, DW> {
     target: S,
     word: DW,
+    /// 語を見つけた辞書の層。0が最も下の層
+    #[new(default)]
+    #[serde(default)]
+    layer: usize,
+    /// 注釈の方針により、注釈しないことになった語
+    #[new(default)]
+    #[serde(default)]
+    suppressed: bool,
+}
+
+impl<S, DW> DictionaryPhrase<S, DW> {
+    pub fn with_layer(mut self, layer: usize) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_suppressed(mut self, suppressed: bool) -> Self {
+        self.suppressed = suppressed;
+        self
+    }
+
+    /// 語を`f`で変換する。一致した層と注釈しないかどうかはそのまま
+    pub fn map_word<T>(self, f: impl FnOnce(DW) -> T) -> DictionaryPhrase<S, T> {
+        DictionaryPhrase {
+            target: self.target,
+            word: f(self.word),
+            layer: self.layer,
+            suppressed: self.suppressed,
+        }
+    }
+}
+
+/// [`crate::ParseOptions`]を指定して解析したときの語。辞書の語のほか、作品中のルビから覚えた読みがある
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GlossaryWord<S, DW> {
+    Dictionary(DW),
+    /// 本文にルビは無く、同じ作品で作者が振ったルビから補った読み
+    Learned {
+        reading: S,
+    },
 }
 
 impl<S: Display, DW> Display for DictionaryPhrase<S, DW> {
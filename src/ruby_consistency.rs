@@ -0,0 +1,279 @@
+//! 作品全体に振られたルビの揺れを調べる
+
+use std::collections::HashMap;
+
+use derive_getters::Getters;
+use derive_new::new;
+
+use crate::{
+    DictionaryWord, DictionaryWordKeyPhrase, GlossaryWord, ParseText, ParsedFragment, Parser,
+    Phrase, parser::auto_glossary::AutoGlossaryIter,
+};
+
+/// 話と、その中の行・列(どちらも1始まり、列は文字数)
+#[derive(new, Getters, Clone, PartialEq, Eq, Debug)]
+pub struct SourceLocation {
+    episode: String,
+    line: usize,
+    column: usize,
+}
+
+/// ある読みと、その読みのルビが振られた場所
+#[derive(new, Getters, Clone, PartialEq, Debug)]
+pub struct ReadingUsage {
+    reading: String,
+    locations: Vec<SourceLocation>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RubyFinding {
+    /// 同じ語に異なる読みのルビが振られている
+    ConflictingReadings {
+        target: String,
+        usages: Vec<ReadingUsage>,
+    },
+    /// ルビが振られている語が、別の場所ではルビ無しで書かれている
+    MissingRuby {
+        target: String,
+        with_ruby: Vec<SourceLocation>,
+        without_ruby: Vec<SourceLocation>,
+    },
+    /// 辞書のルビと異なる読みのルビが振られている
+    DictionaryMismatch {
+        target: String,
+        dictionary_readings: Vec<String>,
+        usages: Vec<ReadingUsage>,
+    },
+}
+
+struct Episode {
+    name: String,
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl Episode {
+    fn location(&self, offset: usize) -> SourceLocation {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        SourceLocation::new(
+            self.name.clone(),
+            line,
+            self.text[line_start..offset].chars().count() + 1,
+        )
+    }
+}
+
+/// 各話を解析してルビを集め、揺れを報告する
+pub struct RubyConsistencyChecker<'p, X = ()> {
+    parser: &'p Parser<X>,
+    episodes: Vec<Episode>,
+}
+
+impl<'p, X> RubyConsistencyChecker<'p, X> {
+    pub fn new(parser: &'p Parser<X>) -> Self {
+        Self {
+            parser,
+            episodes: vec![],
+        }
+    }
+
+    pub fn add_episode(&mut self, name: impl Into<String>, text: impl Into<String>) {
+        let text = text.into();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.episodes.push(Episode {
+            name: name.into(),
+            text,
+            line_starts,
+        });
+    }
+
+    /// 見出しの初出順に、読みの揺れ、辞書との食い違い、ルビの付け忘れの順で報告する
+    pub fn report(&self) -> Vec<RubyFinding> {
+        // 見出し -> 読み -> 場所。どちらも初出順
+        let mut targets: Vec<(String, Vec<ReadingUsage>)> = vec![];
+        let mut target_index = HashMap::new();
+        let mut rubies = vec![];
+        for episode in &self.episodes {
+            for fragment in self.parser.parse_iter(episode.text.as_str()) {
+                let Phrase::Ruby(ruby) = fragment.phrase() else {
+                    continue;
+                };
+                if ruby.target().is_empty() || ruby.ruby().is_empty() {
+                    continue;
+                }
+                let location = episode.location(offset_of(&episode.text, fragment.fragment()));
+                let i = *target_index
+                    .entry(ruby.target().to_string())
+                    .or_insert_with(|| {
+                        targets.push((ruby.target().to_string(), vec![]));
+                        targets.len() - 1
+                    });
+                let usages = &mut targets[i].1;
+                match usages.iter_mut().find(|u| u.reading == *ruby.ruby()) {
+                    Some(usage) => usage.locations.push(location),
+                    None => usages.push(ReadingUsage::new(ruby.ruby().to_string(), vec![location])),
+                }
+                rubies.push(fragment.clone());
+            }
+        }
+
+        let without_ruby = self.bare_occurrences(&rubies);
+        let dictionary_readings = self.dictionary_readings();
+        let mut findings = vec![];
+        for (target, usages) in targets {
+            if usages.len() > 1 {
+                findings.push(RubyFinding::ConflictingReadings {
+                    target: target.clone(),
+                    usages: usages.clone(),
+                });
+            }
+            if let Some(readings) = dictionary_readings.get(target.as_str()) {
+                let mismatched = usages
+                    .iter()
+                    .filter(|u| !readings.contains(&u.reading))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !mismatched.is_empty() {
+                    findings.push(RubyFinding::DictionaryMismatch {
+                        target: target.clone(),
+                        dictionary_readings: readings.clone(),
+                        usages: mismatched,
+                    });
+                }
+            }
+            if let Some(without_ruby) = without_ruby.get(target.as_str()) {
+                findings.push(RubyFinding::MissingRuby {
+                    target: target.clone(),
+                    with_ruby: usages.into_iter().flat_map(|u| u.locations).collect(),
+                    without_ruby: without_ruby.clone(),
+                });
+            }
+        }
+        findings
+    }
+
+    /// ルビの振られた語が平文に現れた場所
+    fn bare_occurrences<'a>(
+        &'a self,
+        rubies: &[ParsedFragment<&'a str, &'a DictionaryWord<X>>],
+    ) -> HashMap<&'a str, Vec<SourceLocation>> {
+        let mut occurrences: HashMap<&str, Vec<SourceLocation>> = HashMap::new();
+        for episode in &self.episodes {
+            let text = episode.text.as_str();
+            for fragment in AutoGlossaryIter::new(self.parser.parse_iter(text), true)
+                .with_pre_scan(rubies.iter().cloned())
+            {
+                if let Phrase::DictionaryWord(dw) = fragment.phrase()
+                    && let GlossaryWord::Learned { .. } = dw.word()
+                {
+                    occurrences
+                        .entry(dw.target())
+                        .or_default()
+                        .push(episode.location(offset_of(text, fragment.fragment())));
+                }
+            }
+        }
+        occurrences
+    }
+
+    /// 辞書の語のうちルビの付いた部分と、語全体の読み
+    fn dictionary_readings(&self) -> HashMap<&'p str, Vec<String>> {
+        let mut readings: HashMap<&'p str, Vec<String>> = HashMap::new();
+        let mut push = |target: &'p str, reading: String| {
+            let entry = readings.entry(target).or_default();
+            if !entry.contains(&reading) {
+                entry.push(reading);
+            }
+        };
+        for word in self.parser.words() {
+            for phrase in word.phrase() {
+                if let DictionaryWordKeyPhrase::Ruby { target, ruby } = phrase {
+                    push(target, ruby.clone());
+                }
+            }
+            if word.phrase().len() > 1
+                && word
+                    .phrase()
+                    .iter()
+                    .any(|p| matches!(p, DictionaryWordKeyPhrase::Ruby { .. }))
+            {
+                push(word.key(), word.reading());
+            }
+        }
+        readings
+    }
+}
+
+fn offset_of(text: &str, fragment: &str) -> usize {
+    fragment.as_ptr() as usize - text.as_ptr() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    fn loc(episode: &str, line: usize, column: usize) -> SourceLocation {
+        SourceLocation::new(episode.into(), line, column)
+    }
+
+    #[gtest]
+    fn report_works() -> anyhow::Result<()> {
+        let parser = Parser::try_new_with_dic(vec![DictionaryWord::new_all(
+            vec![
+                DictionaryWordKeyPhrase::new_ruby("若々".into(), "わかわか".into()),
+                DictionaryWordKeyPhrase::new_plain("しい".into()),
+            ],
+            "".into(),
+            (),
+        )])?;
+        let mut checker = RubyConsistencyChecker::new(&parser);
+        checker.add_episode("第1話", "魔導《まどう》を学ぶ。\n|若々《わかわか》しい");
+        checker.add_episode("第2話", "その魔導《まとう》は\n魔導と|若々《じゃくじゃく》");
+        assert_that!(
+            checker.report(),
+            eq(&vec![
+                RubyFinding::ConflictingReadings {
+                    target: "魔導".into(),
+                    usages: vec![
+                        ReadingUsage::new("まどう".into(), vec![loc("第1話", 1, 1)]),
+                        ReadingUsage::new("まとう".into(), vec![loc("第2話", 1, 3)]),
+                    ],
+                },
+                RubyFinding::MissingRuby {
+                    target: "魔導".into(),
+                    with_ruby: vec![loc("第1話", 1, 1), loc("第2話", 1, 3)],
+                    without_ruby: vec![loc("第2話", 2, 1)],
+                },
+                RubyFinding::ConflictingReadings {
+                    target: "若々".into(),
+                    usages: vec![
+                        ReadingUsage::new("わかわか".into(), vec![loc("第1話", 2, 1)]),
+                        ReadingUsage::new("じゃくじゃく".into(), vec![loc("第2話", 2, 4)]),
+                    ],
+                },
+                RubyFinding::DictionaryMismatch {
+                    target: "若々".into(),
+                    dictionary_readings: vec!["わかわか".into()],
+                    usages: vec![ReadingUsage::new(
+                        "じゃくじゃく".into(),
+                        vec![loc("第2話", 2, 4)]
+                    )],
+                },
+            ])
+        );
+        Ok(())
+    }
+
+    #[gtest]
+    fn report_is_empty_for_consistent_work() {
+        let parser = Parser::default();
+        let mut checker = RubyConsistencyChecker::new(&parser);
+        checker.add_episode("第1話", "玄人《くろうと》");
+        checker.add_episode("第2話", "|玄人《くろうと》の技");
+        assert_that!(checker.report(), is_empty());
+    }
+}